@@ -0,0 +1,605 @@
+/// Rate, in samples per second, that
+/// the APU produces audio at.
+pub const SAMPLE_RATE: u32 = 65536;
+
+/// Number of CPU cycles between samples.
+static CYCLES_PER_SAMPLE: i32 = 64;
+
+/// Number of CPU cycles between frame
+/// sequencer steps (512 Hz).
+static CYCLES_PER_FRAME_STEP: i32 = 8192;
+
+/// Most stereo samples kept before the
+/// oldest are discarded, one second's worth.
+static MAX_BUFFERED_SAMPLES: usize = SAMPLE_RATE as usize * 2;
+
+/// Waveforms for each of the square
+/// channels' duty cycles.
+static DUTY_PATTERNS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0]
+];
+
+/// Divisors selected by the lower
+/// 3 bits of NR43.
+static NOISE_DIVISORS: [i32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+/// Bits that always read back as 1 for
+/// each register from 0xFF10 to 0xFF2F.
+static READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF,
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+    0xFF, 0xFF, 0x00, 0x00, 0xBF,
+    0x00, 0x00, 0x70,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+];
+
+/// Counts down to silence a channel
+/// after a set amount of time.
+#[derive(Default)]
+struct LengthCounter {
+    counter: u16,
+    enabled: bool
+}
+
+impl LengthCounter {
+
+    /// Loads the counter from the length
+    /// bits written to NRx1.
+    fn load(&mut self, max: u16, length: u16) {
+        self.counter = max - length;
+    }
+
+    /// Reloads an expired counter when
+    /// the channel is triggered.
+    fn trigger(&mut self, max: u16) {
+        if self.counter == 0 {
+            self.counter = max;
+        }
+    }
+
+    /// Clocks the counter, returning false
+    /// when the channel should be disabled.
+    fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            if self.counter == 0 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Raises or lowers a channel's
+/// volume over time.
+#[derive(Default)]
+struct VolumeEnvelope {
+    register: u8,
+    volume: u8,
+    timer: u8
+}
+
+impl VolumeEnvelope {
+
+    /// Returns whether the channel's DAC is on,
+    /// which is when the upper 5 bits are set.
+    fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    /// Restarts the envelope from NRx2.
+    fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.register & 0x07;
+    }
+
+    /// Moves the volume one step when
+    /// the period has elapsed.
+    fn clock(&mut self) {
+        let period = self.register & 0x07;
+        if period == 0 {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = period;
+            if self.register & 0x08 != 0 && self.volume < 15 {
+                self.volume += 1;
+            }
+            else if self.register & 0x08 == 0 && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+/// Square wave channel, used for channels 1 and 2.
+/// Only channel 1 makes use of the sweep.
+#[derive(Default)]
+struct SquareChannel {
+    enabled: bool,
+    duty: u8,
+    duty_step: usize,
+    frequency: u16,
+    timer: i32,
+    length: LengthCounter,
+    envelope: VolumeEnvelope,
+    sweep_register: u8,
+    sweep_enabled: bool,
+    sweep_timer: u8,
+    shadow_frequency: u16
+}
+
+impl SquareChannel {
+
+    /// Returns the sweep's next frequency, disabling
+    /// the channel if it overflows 11 bits.
+    fn calculate_sweep(&mut self) -> u16 {
+        let shift = self.sweep_register & 0x07;
+        let delta = self.shadow_frequency >> shift;
+        let new_frequency = if self.sweep_register & 0x08 != 0 {
+            self.shadow_frequency.wrapping_sub(delta)
+        }
+        else {
+            self.shadow_frequency + delta
+        };
+
+        if new_frequency > 2047 {
+            self.enabled = false;
+        }
+        new_frequency
+    }
+
+    /// Restarts the channel.
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger(64);
+        self.timer = (2048 - self.frequency as i32) * 4;
+        self.envelope.trigger();
+
+        // Reload sweep
+        let period = (self.sweep_register >> 4) & 0x07;
+        let shift = self.sweep_register & 0x07;
+        self.shadow_frequency = self.frequency;
+        self.sweep_timer = if period == 0 { 8 } else { period };
+        self.sweep_enabled = period != 0 || shift != 0;
+        if shift != 0 {
+            self.calculate_sweep();
+        }
+    }
+
+    /// Clocks the frequency sweep.
+    fn clock_sweep(&mut self) {
+        if self.sweep_timer > 0 {
+            self.sweep_timer -= 1;
+        }
+        if self.sweep_timer != 0 {
+            return;
+        }
+
+        let period = (self.sweep_register >> 4) & 0x07;
+        self.sweep_timer = if period == 0 { 8 } else { period };
+
+        if self.sweep_enabled && period != 0 {
+            let new_frequency = self.calculate_sweep();
+            if new_frequency <= 2047 && self.sweep_register & 0x07 != 0 {
+                self.shadow_frequency = new_frequency;
+                self.frequency = new_frequency;
+
+                // Check for overflow again with the new frequency
+                self.calculate_sweep();
+            }
+        }
+    }
+
+    /// Advances the waveform by the given cycles.
+    fn update(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += (2048 - self.frequency as i32) * 4;
+            self.duty_step = (self.duty_step + 1) % 8;
+        }
+    }
+
+    /// Returns the channel's output from 0 to 15.
+    fn output(&self) -> u8 {
+        if self.enabled && DUTY_PATTERNS[self.duty as usize][self.duty_step] == 1 {
+            self.envelope.volume
+        }
+        else {
+            0
+        }
+    }
+}
+
+/// Channel 3, which plays back the
+/// samples stored in wave RAM.
+#[derive(Default)]
+struct WaveChannel {
+    enabled: bool,
+    dac_enabled: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: i32,
+    position: usize,
+    length: LengthCounter,
+    wave_ram: [u8; 16]
+}
+
+impl WaveChannel {
+
+    /// Restarts the channel.
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger(256);
+        self.timer = (2048 - self.frequency as i32) * 2;
+        self.position = 0;
+    }
+
+    /// Advances the waveform by the given cycles.
+    fn update(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += (2048 - self.frequency as i32) * 2;
+            self.position = (self.position + 1) % 32;
+        }
+    }
+
+    /// Returns the channel's output from 0 to 15.
+    fn output(&self) -> u8 {
+        if !self.enabled || self.volume_code == 0 {
+            return 0;
+        }
+
+        let byte = self.wave_ram[self.position / 2];
+        let sample = if self.position.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+        sample >> (self.volume_code - 1)
+    }
+}
+
+/// Channel 4, which produces noise from a
+/// linear feedback shift register.
+#[derive(Default)]
+struct NoiseChannel {
+    enabled: bool,
+    polynomial: u8,
+    timer: i32,
+    lfsr: u16,
+    length: LengthCounter,
+    envelope: VolumeEnvelope
+}
+
+impl NoiseChannel {
+
+    /// Returns the number of cycles between
+    /// shifts of the register.
+    fn period(&self) -> i32 {
+        NOISE_DIVISORS[(self.polynomial & 0x07) as usize] << (self.polynomial >> 4)
+    }
+
+    /// Restarts the channel.
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger(64);
+        self.timer = self.period();
+        self.envelope.trigger();
+        self.lfsr = 0x7FFF;
+    }
+
+    /// Advances the shift register by the given cycles.
+    fn update(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += self.period();
+
+            let feedback = (self.lfsr & 1) ^ ((self.lfsr >> 1) & 1);
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+
+            // 7-bit mode also feeds back into bit 6
+            if self.polynomial & 0x08 != 0 {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+            }
+        }
+    }
+
+    /// Returns the channel's output from 0 to 15.
+    fn output(&self) -> u8 {
+        if self.enabled && self.lfsr & 1 == 0 {
+            self.envelope.volume
+        }
+        else {
+            0
+        }
+    }
+}
+
+/// The audio processing unit, made up of two square
+/// channels, a wave channel and a noise channel.
+pub struct Apu {
+    powered_on: bool,
+    square_1: SquareChannel,
+    square_2: SquareChannel,
+    wave: WaveChannel,
+    noise: NoiseChannel,
+    registers: [u8; 0x20],
+    frame_step: u8,
+    frame_counter: i32,
+    sample_counter: i32,
+    samples: Vec<f32>
+}
+
+impl Apu {
+
+    /// Default constructor, for an APU
+    /// that is powered off.
+    pub fn new() -> Apu {
+        Apu {
+            powered_on: false,
+            square_1: SquareChannel::default(),
+            square_2: SquareChannel::default(),
+            wave: WaveChannel::default(),
+            noise: NoiseChannel::default(),
+            registers: [0; 0x20],
+            frame_step: 0,
+            frame_counter: CYCLES_PER_FRAME_STEP,
+            sample_counter: CYCLES_PER_SAMPLE,
+            samples: Vec::new()
+        }
+    }
+
+    /// Reads a sound register or wave RAM byte.
+    pub fn read_register(&mut self, address: u16) -> u8 {
+        match address {
+            0xFF30..=0xFF3F => self.wave.wave_ram[(address - 0xFF30) as usize],
+
+            0xFF26 => {
+                let mut status = if self.powered_on { 0x80 } else { 0 };
+                if self.square_1.enabled { status |= 1; }
+                if self.square_2.enabled { status |= 1 << 1; }
+                if self.wave.enabled { status |= 1 << 2; }
+                if self.noise.enabled { status |= 1 << 3; }
+                status | READ_MASKS[0x16]
+            },
+
+            0xFF10..=0xFF2F => {
+                let index = (address - 0xFF10) as usize;
+                self.registers[index] | READ_MASKS[index]
+            },
+
+            _ => 0xFF
+        }
+    }
+
+    /// Writes to a sound register or wave RAM byte.
+    pub fn write_register(&mut self, address: u16, byte: u8) {
+
+        // Wave RAM is accessible even when powered off
+        if (0xFF30..=0xFF3F).contains(&address) {
+            self.wave.wave_ram[(address - 0xFF30) as usize] = byte;
+            return;
+        }
+
+        if address == 0xFF26 {
+            self.set_power(byte & 0x80 != 0);
+            return;
+        }
+
+        if !self.powered_on || !(0xFF10..=0xFF2F).contains(&address) {
+            return;
+        }
+        self.registers[(address - 0xFF10) as usize] = byte;
+
+        match address {
+
+            // Channel 1
+            0xFF10 => self.square_1.sweep_register = byte,
+            0xFF11 => {
+                self.square_1.duty = byte >> 6;
+                self.square_1.length.load(64, (byte & 0x3F) as u16);
+            },
+            0xFF12 => {
+                self.square_1.envelope.register = byte;
+                if !self.square_1.envelope.dac_enabled() {
+                    self.square_1.enabled = false;
+                }
+            },
+            0xFF13 => self.square_1.frequency = (self.square_1.frequency & 0x700) | byte as u16,
+            0xFF14 => {
+                self.square_1.frequency = (self.square_1.frequency & 0xFF) | ((byte as u16 & 0x07) << 8);
+                self.square_1.length.enabled = byte & 0x40 != 0;
+                if byte & 0x80 != 0 {
+                    self.square_1.trigger();
+                }
+            },
+
+            // Channel 2
+            0xFF16 => {
+                self.square_2.duty = byte >> 6;
+                self.square_2.length.load(64, (byte & 0x3F) as u16);
+            },
+            0xFF17 => {
+                self.square_2.envelope.register = byte;
+                if !self.square_2.envelope.dac_enabled() {
+                    self.square_2.enabled = false;
+                }
+            },
+            0xFF18 => self.square_2.frequency = (self.square_2.frequency & 0x700) | byte as u16,
+            0xFF19 => {
+                self.square_2.frequency = (self.square_2.frequency & 0xFF) | ((byte as u16 & 0x07) << 8);
+                self.square_2.length.enabled = byte & 0x40 != 0;
+                if byte & 0x80 != 0 {
+                    self.square_2.trigger();
+                }
+            },
+
+            // Channel 3
+            0xFF1A => {
+                self.wave.dac_enabled = byte & 0x80 != 0;
+                if !self.wave.dac_enabled {
+                    self.wave.enabled = false;
+                }
+            },
+            0xFF1B => self.wave.length.load(256, byte as u16),
+            0xFF1C => self.wave.volume_code = (byte >> 5) & 0x03,
+            0xFF1D => self.wave.frequency = (self.wave.frequency & 0x700) | byte as u16,
+            0xFF1E => {
+                self.wave.frequency = (self.wave.frequency & 0xFF) | ((byte as u16 & 0x07) << 8);
+                self.wave.length.enabled = byte & 0x40 != 0;
+                if byte & 0x80 != 0 {
+                    self.wave.trigger();
+                }
+            },
+
+            // Channel 4
+            0xFF20 => self.noise.length.load(64, (byte & 0x3F) as u16),
+            0xFF21 => {
+                self.noise.envelope.register = byte;
+                if !self.noise.envelope.dac_enabled() {
+                    self.noise.enabled = false;
+                }
+            },
+            0xFF22 => self.noise.polynomial = byte,
+            0xFF23 => {
+                self.noise.length.enabled = byte & 0x40 != 0;
+                if byte & 0x80 != 0 {
+                    self.noise.trigger();
+                }
+            },
+
+            // NR50 and NR51 are only read back when mixing
+            _ => {}
+        }
+    }
+
+    /// Turns the APU on or off. Turning it off
+    /// clears every register but wave RAM.
+    fn set_power(&mut self, powered_on: bool) {
+        if self.powered_on && !powered_on {
+            let wave_ram = self.wave.wave_ram;
+            self.square_1 = SquareChannel::default();
+            self.square_2 = SquareChannel::default();
+            self.wave = WaveChannel::default();
+            self.wave.wave_ram = wave_ram;
+            self.noise = NoiseChannel::default();
+            self.registers = [0; 0x20];
+        }
+        else if !self.powered_on && powered_on {
+            self.frame_step = 0;
+        }
+        self.powered_on = powered_on;
+    }
+
+    /// Advances the frame sequencer, which clocks the
+    /// length counters, sweep and envelopes.
+    fn clock_frame_sequencer(&mut self) {
+
+        // Length counters at 256 Hz
+        if self.frame_step.is_multiple_of(2) {
+            if !self.square_1.length.clock() { self.square_1.enabled = false; }
+            if !self.square_2.length.clock() { self.square_2.enabled = false; }
+            if !self.wave.length.clock() { self.wave.enabled = false; }
+            if !self.noise.length.clock() { self.noise.enabled = false; }
+        }
+
+        // Sweep at 128 Hz
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.square_1.clock_sweep();
+        }
+
+        // Envelopes at 64 Hz
+        if self.frame_step == 7 {
+            self.square_1.envelope.clock();
+            self.square_2.envelope.clock();
+            self.noise.envelope.clock();
+        }
+
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    /// Mixes the channels into a left and right sample
+    /// using the panning in NR51 and volume in NR50.
+    fn mix(&self) -> (f32, f32) {
+        let panning = self.registers[0x15];
+        let volume = self.registers[0x14];
+        let dac_outputs = [
+            (self.square_1.envelope.dac_enabled(), self.square_1.output()),
+            (self.square_2.envelope.dac_enabled(), self.square_2.output()),
+            (self.wave.dac_enabled, self.wave.output()),
+            (self.noise.envelope.dac_enabled(), self.noise.output())
+        ];
+
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (i, &(dac_enabled, output)) in dac_outputs.iter().enumerate() {
+            if !dac_enabled {
+                continue;
+            }
+
+            // Convert the 0-15 digital value to -1.0 to 1.0
+            let analog = output as f32 / 7.5 - 1.0;
+            if panning & (1 << (i + 4)) != 0 {
+                left += analog;
+            }
+            if panning & (1 << i) != 0 {
+                right += analog;
+            }
+        }
+
+        let left_volume = (((volume >> 4) & 0x07) + 1) as f32 / 8.0;
+        let right_volume = ((volume & 0x07) + 1) as f32 / 8.0;
+        (left / 4.0 * left_volume, right / 4.0 * right_volume)
+    }
+
+    /// Updates the channels and frame sequencer
+    /// based on the current amount of CPU cycles,
+    /// collecting samples as they're produced.
+    pub fn update(&mut self, cycles: i32) {
+        if self.powered_on {
+            self.square_1.update(cycles);
+            self.square_2.update(cycles);
+            self.wave.update(cycles);
+            self.noise.update(cycles);
+
+            self.frame_counter -= cycles;
+            while self.frame_counter <= 0 {
+                self.frame_counter += CYCLES_PER_FRAME_STEP;
+                self.clock_frame_sequencer();
+            }
+        }
+
+        self.sample_counter -= cycles;
+        while self.sample_counter <= 0 {
+            self.sample_counter += CYCLES_PER_SAMPLE;
+
+            // Drop samples nobody is collecting
+            if self.samples.len() >= MAX_BUFFERED_SAMPLES {
+                self.samples.drain(..MAX_BUFFERED_SAMPLES / 2);
+            }
+
+            let (left, right) = if self.powered_on { self.mix() } else { (0.0, 0.0) };
+            self.samples.push(left);
+            self.samples.push(right);
+        }
+    }
+
+    /// Takes the interleaved left and right samples
+    /// produced since the last call.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(self.samples.len());
+        samples.append(&mut self.samples);
+        samples
+    }
+}
+
+impl Default for Apu {
+    fn default() -> Apu {
+        Apu::new()
+    }
+}
@@ -1,183 +1,441 @@
-use std::io;
+use cartridge_header::*;
+use error::*;
+use real_time_clock::*;
+
+use std::fs;
 use std::fs::File;
 use std::io::Read;
-use std::env;
+use std::path::{Path, PathBuf};
 
 #[derive(PartialEq)]
 enum BankingType {
     NoBanking,
     MBC1,
-    MBC2
+    MBC2,
+    MBC3,
+    MBC5
 }
 
 pub struct Cartridge {
+    header: CartridgeHeader,
     rom: Vec<u8>,
-    ram_banks: [u8; 0x8000],
+    ram_banks: Vec<u8>,
+    has_battery: bool,
+    save_path: Option<PathBuf>,
+    ram_dirty: bool,
     banking_type: BankingType,
-    current_rom_bank: u8,
+    current_rom_bank: u16,
     current_ram_bank: u8,
-    rom_banking_mode: bool,
+    lo_rom_bank: u16,
+    mbc1_bank1: u8,
+    mbc1_bank2: u8,
+    mbc1_mode: bool,
+    is_multicart: bool,
+    has_clock: bool,
+    clock: RealTimeClock,
+    selected_clock_register: Option<u8>,
+    clock_latch_primed: bool,
+    has_rumble: bool,
+    rumble_active: bool,
+    rumble_callback: Option<Box<dyn FnMut(bool) + Send>>,
     pub ram_write_enabled: bool
 }
 
 impl Cartridge {
 
-    /// Default constructor.
-    pub fn new() -> Cartridge {
-        let mut cartridge = Cartridge {
-            rom: Vec::new(),
-            ram_banks: [0; 0x8000],
-            banking_type: BankingType::NoBanking,
-            current_rom_bank: 1,
-            current_ram_bank: 0,
-            rom_banking_mode: true,
-            ram_write_enabled: false
-        };
+    /// Creates a cartridge from the bytes of a ROM image.
+    pub fn from_bytes(rom: &[u8]) -> Result<Cartridge, EmulatorError> {
 
-        // Set rom to bytes from file
-        let args: Vec<String> = env::args().collect();
-        let rom = cartridge.read_rom(&args[1]);
-        let rom = match rom {
-            Ok(rom) => rom,
-            Err(e) => panic!("{}", e),
-        };
-        cartridge.rom = rom;
+        // Reject ROMs smaller than two banks, larger than
+        // possible, or whose size is not a power of two
+        if rom.len() < 0x8000 || rom.len() > 0x800000 || (rom.len() & (rom.len() - 1)) != 0 {
+            return Err(EmulatorError::InvalidRomSize { size: rom.len() });
+        }
+
+        let header = CartridgeHeader::parse(rom)?;
 
         // Set rom banking type
-        match cartridge.rom[0x147] {
-            0 => cartridge.banking_type = BankingType::NoBanking,
-            1 | 2 | 3 => cartridge.banking_type = BankingType::MBC1,
-            4 | 5 | 6 => cartridge.banking_type = BankingType::MBC2,
-            _ => panic!("Banking type is currently not supported. Value at 0x147 was 0x{:02X}", cartridge.rom[0x147])
+        let banking_type = match header.cartridge_type {
+            0 => BankingType::NoBanking,
+            1..=3 => BankingType::MBC1,
+            4..=6 => BankingType::MBC2,
+            0x0F..=0x13 => BankingType::MBC3,
+            0x19..=0x1E => BankingType::MBC5,
+            cartridge_type => return Err(EmulatorError::UnsupportedCartridgeType { cartridge_type })
+        };
+
+        // MBC2 has 512 half-bytes of RAM built in,
+        // otherwise the size is given by 0x149
+        let ram_size = if banking_type == BankingType::MBC2 {
+            0x200
         }
-        cartridge
+        else {
+            header.get_ram_size()
+        };
+
+        let rom_data = rom.to_vec();
+        let is_multicart = banking_type == BankingType::MBC1 && Cartridge::is_mbc1_multicart(&rom_data);
+        let cartridge_type = header.cartridge_type;
+
+        Ok(Cartridge {
+            header,
+            rom: rom_data,
+            ram_banks: vec![0; ram_size],
+            has_battery: Cartridge::type_has_battery(cartridge_type),
+            save_path: None,
+            ram_dirty: false,
+            banking_type,
+            current_rom_bank: 1,
+            current_ram_bank: 0,
+            lo_rom_bank: 0,
+            mbc1_bank1: 1,
+            mbc1_bank2: 0,
+            mbc1_mode: false,
+            is_multicart,
+            has_clock: cartridge_type == 0x0F || cartridge_type == 0x10,
+            clock: RealTimeClock::new(),
+            selected_clock_register: None,
+            clock_latch_primed: false,
+            has_rumble: (0x1C..=0x1E).contains(&cartridge_type),
+            rumble_active: false,
+            rumble_callback: None,
+            ram_write_enabled: false
+        })
     }
 
-    /// Reads a rom file's bytes to a vector on success.
-    pub fn read_rom(&mut self, location: &str) -> io::Result<Vec<u8>> {
-        let mut rom = File::open(location)?;
+    /// Creates a cartridge from a ROM file on disk.
+    pub fn from_path(path: &Path) -> Result<Cartridge, EmulatorError> {
+        let mut rom = File::open(path)?;
         let mut buffer = Vec::new();
         rom.read_to_end(&mut buffer)?;
-        
-        // Panic if ROM has more bytes than possible
-        // or is amount of bytes is not a power of two
-        if buffer.len() > 0x200000 || (buffer.len() & (buffer.len() - 1)) != 0 {
-            panic!("Invalid ROM size, {} bytes", buffer.len());
+        let mut cartridge = Cartridge::from_bytes(&buffer)?;
+
+        // Keep battery backed RAM in a .sav next to the ROM
+        if cartridge.has_battery {
+            let save_path = path.with_extension("sav");
+            if save_path.exists() {
+                let data = fs::read(&save_path)?;
+                cartridge.load_ram_data(&data);
+            }
+            cartridge.save_path = Some(save_path);
         }
-        Ok(buffer)
+        Ok(cartridge)
     }
 
-    /// Updates ability to write to RAM based on
-    /// the value of the lower half of address.
-    pub fn update_ram_writing(&mut self, address: u16, byte: u8) {
-
-        // Stop if using MBC2 and the 4th address bit is 1
-        if self.banking_type == BankingType::MBC2 {
-            if (address & 0x08) >> 3 == 1 {
-                return;
-            }
+    /// Returns whether an MBC1 ROM is a multicart, which
+    /// wires BANK2 one bit lower to select between games.
+    /// These are 1 MiB and repeat the Nintendo logo at
+    /// the start of the second game in bank 0x10.
+    pub fn is_mbc1_multicart(rom: &[u8]) -> bool {
+        if rom.len() != 0x100000 {
+            return false;
         }
 
-        if (byte & 0x0F) == 0x0A {
-            self.ram_write_enabled = true;
+        let logo = &rom[0x104..0x134];
+        let game_start = 0x10 * 0x4000;
+        &rom[game_start + 0x104..game_start + 0x134] == logo
+    }
+
+    /// Returns whether a cartridge type from
+    /// 0x147 has a battery to keep its RAM.
+    pub fn type_has_battery(cartridge_type: u8) -> bool {
+        matches!(cartridge_type, 0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFF)
+    }
+
+    /// Getter for the cartridge's header.
+    pub fn get_header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    /// Getter for whether the cartridge has a battery.
+    pub fn get_has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    /// Setter for the file battery backed
+    /// RAM is saved to.
+    pub fn set_save_path(&mut self, save_path: Option<PathBuf>) {
+        self.save_path = save_path;
+    }
+
+    /// Returns the contents of cartridge RAM.
+    pub fn get_ram_data(&self) -> &[u8] {
+        &self.ram_banks
+    }
+
+    /// Replaces the contents of cartridge RAM. For cartridges
+    /// with a clock, the clock is restored from any footer
+    /// that follows the RAM.
+    pub fn load_ram_data(&mut self, data: &[u8]) {
+        let ram_size = self.ram_banks.len();
+        let length = data.len().min(ram_size);
+        self.ram_banks[..length].copy_from_slice(&data[..length]);
+        self.ram_dirty = false;
+
+        if self.has_clock && data.len() >= ram_size + RTC_SAVE_SIZE {
+            self.clock.load_save_data(&data[ram_size..]);
         }
-        else if (byte & 0x0F) == 0x00 {
-            self.ram_write_enabled = false;
+    }
+
+    /// Writes battery backed RAM to the save file
+    /// if it has changed since it was last saved.
+    /// The clock changes every second, so cartridges
+    /// with one are always written.
+    pub fn save_ram(&mut self) -> Result<(), EmulatorError> {
+        if !self.has_battery || (!self.ram_dirty && !self.has_clock) {
+            return Ok(());
         }
-    }    
 
-    /// Changes lower bits of the current ROM bank.
-    pub fn change_lo_rom_bank(&mut self, byte: u8) {
-        
-        // Set bank to lower half of byte if MBC2
-        if self.banking_type == BankingType::MBC2 {
-            self.current_rom_bank = byte & 0x0F;
-            if self.current_rom_bank == 0 {
-                self.current_rom_bank += 1;
+        if let Some(ref save_path) = self.save_path {
+            let mut data = self.ram_banks.clone();
+            if self.has_clock {
+                data.extend_from_slice(&self.clock.to_save_data());
             }
-            return;
+
+            // Write to a temporary file first so a crash
+            // part way through can't corrupt the save
+            let temp_path = save_path.with_extension("sav.tmp");
+            fs::write(&temp_path, &data)?;
+            fs::rename(&temp_path, save_path)?;
         }
+        self.ram_dirty = false;
+        Ok(())
+    }
 
-        // Sets bank's first five bits otherwise
-        self.current_rom_bank &= 0xE0;
-        self.current_rom_bank |= byte & 0x1F;
-        if self.current_rom_bank == 0 {
-            self.current_rom_bank += 1;
+    /// Updates ability to write to RAM based on
+    /// the value of the lower half of byte.
+    pub fn update_ram_writing(&mut self, byte: u8) {
+        self.ram_write_enabled = (byte & 0x0F) == 0x0A;
+    }
+
+    /// Writes to MBC2's registers, which are both mapped
+    /// across 0x0000-0x3FFF. Bit 8 of the address selects
+    /// the ROM bank when set and RAM enable otherwise.
+    pub fn write_mbc2_register(&mut self, address: u16, byte: u8) {
+        if (address & 0x0100) != 0 {
+            self.change_mbc2_rom_bank(byte);
+        }
+        else {
+            self.update_ram_writing(byte);
         }
     }
 
-    /// Sets ROM bank's upper 3 bits to the upper 3
-    /// bits of byte.
-    pub fn change_hi_rom_bank(&mut self, byte: u8) {
-        self.current_rom_bank &= 0x1F;
-        let upper_three = byte & 0xE0;
-        self.current_rom_bank |= upper_three;
+    /// Sets the MBC2 ROM bank to the lower half of byte,
+    /// where bank 0 maps to bank 1 before wrapping to
+    /// the ROM's size.
+    pub fn change_mbc2_rom_bank(&mut self, byte: u8) {
+        self.current_rom_bank = (byte & 0x0F) as u16;
         if self.current_rom_bank == 0 {
             self.current_rom_bank += 1;
         }
+        self.current_rom_bank &= self.get_rom_bank_mask();
     }
 
-    /// Sets RAM bank to lower 2 bits of byte.
-    pub fn change_ram_bank(&mut self, byte: u8) {
-        self.current_ram_bank = byte & 0x03;
+    /// Sets MBC1's BANK1 register to the lower 5 bits
+    /// of byte. A value of 0 is treated as 1, even
+    /// when the ROM is too small to use every bit.
+    pub fn set_mbc1_bank1(&mut self, byte: u8) {
+        self.mbc1_bank1 = byte & 0x1F;
+        if self.mbc1_bank1 == 0 {
+            self.mbc1_bank1 = 1;
+        }
+        self.update_mbc1_banks();
     }
 
-    /// Determines if ROM or RAM banking mode should
-    /// be used based on the LSB of byte.
+    /// Sets MBC1's BANK2 register to the lower 2 bits
+    /// of byte. These select the upper ROM bank bits
+    /// or the RAM bank, depending on the mode.
+    pub fn set_mbc1_bank2(&mut self, byte: u8) {
+        self.mbc1_bank2 = byte & 0x03;
+        self.update_mbc1_banks();
+    }
+
+    /// Sets MBC1's MODE register to the LSB of byte.
+    /// In mode 1, BANK2 also applies to 0x0000-0x3FFF
+    /// and to cartridge RAM.
     pub fn set_banking_mode(&mut self, byte: u8) {
-        self.rom_banking_mode = if (byte & 0x01) == 0 {
-            true
-        } 
+        self.mbc1_mode = (byte & 0x01) != 0;
+        self.update_mbc1_banks();
+    }
+
+    /// Recomputes the effective MBC1 banks from
+    /// BANK1, BANK2 and MODE.
+    fn update_mbc1_banks(&mut self) {
+
+        // Multicarts only connect 4 bits of BANK1
+        let (bank1, bank2_shift) = if self.is_multicart {
+            (self.mbc1_bank1 & 0x0F, 4)
+        }
+        else {
+            (self.mbc1_bank1, 5)
+        };
+        let upper_bits = (self.mbc1_bank2 as u16) << bank2_shift;
+
+        let bank_mask = self.get_rom_bank_mask();
+        self.current_rom_bank = (upper_bits | bank1 as u16) & bank_mask;
+        self.lo_rom_bank = if self.mbc1_mode { upper_bits & bank_mask } else { 0 };
+
+        // Only carts with more than one RAM
+        // bank connect BANK2 to RAM
+        self.current_ram_bank = if self.mbc1_mode && self.ram_banks.len() > 0x2000 {
+            self.mbc1_bank2
+        }
         else {
-            false
+            0
         };
+    }
 
-        // Update RAM bank to 0 if in ROM banking mode
-        if self.rom_banking_mode {
-            self.current_ram_bank = 0;
+    /// Sets the MBC3 ROM bank to the lower 7 bits of
+    /// byte, where bank 0 maps to bank 1 before
+    /// wrapping to the ROM's size.
+    pub fn change_mbc3_rom_bank(&mut self, byte: u8) {
+        self.current_rom_bank = (byte & 0x7F) as u16;
+        if self.current_rom_bank == 0 {
+            self.current_rom_bank = 1;
+        }
+        self.current_rom_bank &= self.get_rom_bank_mask();
+    }
+
+    /// Maps either a RAM bank, for 0x00-0x03, or a
+    /// clock register, for 0x08-0x0C, to 0xA000-0xBFFF.
+    pub fn select_mbc3_ram_bank(&mut self, byte: u8) {
+        match byte {
+            0x00..=0x03 => {
+                self.current_ram_bank = byte;
+                self.selected_clock_register = None;
+            },
+            0x08..=0x0C if self.has_clock => {
+                self.selected_clock_register = Some(byte);
+            },
+            _ => {}
+        }
+    }
+
+    /// Latches the clock when 0x00 then 0x01
+    /// are written to 0x6000-0x7FFF.
+    pub fn latch_clock(&mut self, byte: u8) {
+        if self.clock_latch_primed && byte == 0x01 {
+            self.clock.latch();
+        }
+        self.clock_latch_primed = byte == 0x00;
+    }
+
+    /// Sets the lower 8 bits of the MBC5 ROM bank.
+    /// Unlike other MBCs, bank 0 can be selected.
+    pub fn change_mbc5_lo_rom_bank(&mut self, byte: u8) {
+        self.current_rom_bank = ((self.current_rom_bank & 0x100) | byte as u16) & self.get_rom_bank_mask();
+    }
+
+    /// Sets the 9th bit of the MBC5 ROM bank
+    /// to the LSB of byte.
+    pub fn change_mbc5_hi_rom_bank(&mut self, byte: u8) {
+        self.current_rom_bank = ((self.current_rom_bank & 0xFF) | ((byte as u16 & 0x01) << 8)) & self.get_rom_bank_mask();
+    }
+
+    /// Sets the MBC5 RAM bank to the lower 4 bits of
+    /// byte. On rumble cartridges bit 3 drives the
+    /// motor instead, leaving 3 bits for the bank.
+    pub fn change_mbc5_ram_bank(&mut self, byte: u8) {
+        if self.has_rumble {
+            self.current_ram_bank = byte & 0x07;
+            self.set_rumble((byte & 0x08) != 0);
+        }
+        else {
+            self.current_ram_bank = byte & 0x0F;
+        }
+    }
+
+    /// Updates the rumble motor, notifying the
+    /// callback when the state changes.
+    fn set_rumble(&mut self, active: bool) {
+        if self.rumble_active == active {
+            return;
+        }
+
+        self.rumble_active = active;
+        if let Some(ref mut callback) = self.rumble_callback {
+            callback(active);
+        }
+    }
+
+    /// Getter for whether the rumble motor is on.
+    pub fn is_rumbling(&self) -> bool {
+        self.rumble_active
+    }
+
+    /// Sets a function to be called with the new
+    /// state whenever the rumble motor turns on or off.
+    pub fn set_rumble_callback(&mut self, callback: Option<Box<dyn FnMut(bool) + Send>>) {
+        self.rumble_callback = callback;
+    }
+
+    /// Updates the cartridge's clock, if it has
+    /// one, based on the current amount of CPU cycles.
+    pub fn update_clock(&mut self, cycles: i32) {
+        if self.has_clock {
+            self.clock.update(cycles);
         }
     }
 
     /// Handles banks based upon the address given.
-    pub fn manage_banking(&mut self, address: u16, byte: u8) {
+    pub fn manage_banking(&mut self, address: u16, byte: u8) -> Result<(), EmulatorError> {
+        if self.banking_type == BankingType::MBC2 && address < 0x4000 {
+            self.write_mbc2_register(address, byte);
+            return Ok(());
+        }
 
         // Enable RAM bank writes
         match address {
-            0...0x1FFF => {
-                if self.banking_type == BankingType::MBC1 || self.banking_type == BankingType::MBC2 {
-                    self.update_ram_writing(address, byte);
+            0..=0x1FFF => {
+                if self.banking_type != BankingType::NoBanking {
+                    self.update_ram_writing(byte);
                 }
             },
 
             // Change low bits of ROM bank
-            0x2000...0x3FFF => {
-                if self.banking_type == BankingType::MBC1 || self.banking_type == BankingType::MBC2 {
-                    self.change_lo_rom_bank(byte);
+            0x2000..=0x3FFF => {
+                if self.banking_type == BankingType::MBC1 {
+                    self.set_mbc1_bank1(byte);
+                }
+                else if self.banking_type == BankingType::MBC3 {
+                    self.change_mbc3_rom_bank(byte);
+                }
+                else if self.banking_type == BankingType::MBC5 {
+                    if address < 0x3000 {
+                        self.change_mbc5_lo_rom_bank(byte);
+                    }
+                    else {
+                        self.change_mbc5_hi_rom_bank(byte);
+                    }
                 }
             },
 
             // Change RAM bank or change high bits of ROM bank
-            0x4000...0x5FFF => {
+            0x4000..=0x5FFF => {
                 if self.banking_type == BankingType::MBC1 {
-                    if self.rom_banking_mode {
-                        self.change_hi_rom_bank(byte);
-                    }
-                    else {
-                        self.change_ram_bank(byte);
-                    }
+                    self.set_mbc1_bank2(byte);
+                }
+                else if self.banking_type == BankingType::MBC3 {
+                    self.select_mbc3_ram_bank(byte);
+                }
+                else if self.banking_type == BankingType::MBC5 {
+                    self.change_mbc5_ram_bank(byte);
                 }
             },
 
-            // Update banking mode
-            0x6000...0x7FFF => {
+            // Update banking mode or latch the clock
+            0x6000..=0x7FFF => {
                 if self.banking_type == BankingType::MBC1 {
                     self.set_banking_mode(byte);
                 }
+                else if self.banking_type == BankingType::MBC3 {
+                    self.latch_clock(byte);
+                }
             },
 
-            _ => panic!("Inaccessable address: 0x{:04X}", address)
+            _ => return Err(EmulatorError::InvalidBankingAddress { address, byte })
         }
-
+        Ok(())
     }
 
     /// Returns the byte in rom at a given address.
@@ -185,21 +443,86 @@ impl Cartridge {
         self.rom[address as usize]
     }
 
-    /// Returns the byte in a ram bank at a given address.
-    pub fn get_ram(&mut self, address: u16) -> u8 {
-        self.ram_banks[address as usize]
+    /// Returns the number of bytes in rom.
+    pub fn get_rom_size(&mut self) -> usize {
+        self.rom.len()
     }
 
-    /// Sets the byte in a ram bank at a given address.
-    pub fn set_ram(&mut self, address: u16, byte: u8) {
-        self.ram_banks[address as usize] = byte;
+    /// Returns the mask applied to ROM bank numbers. Unused
+    /// bank bits aren't connected to the ROM, so the bank
+    /// wraps to the size the header gives, or to the image's
+    /// size if that's larger. Banks past the end of an image
+    /// shorter than its header are left for reads to catch.
+    fn get_rom_bank_mask(&self) -> u16 {
+        let rom_size = match self.header.get_rom_size() {
+            Some(rom_size) if rom_size > self.rom.len() => rom_size,
+            _ => self.rom.len()
+        };
+        (rom_size / 0x4000 - 1) as u16
+    }
+
+    /// Returns the byte at an address from 0xA000 to
+    /// 0xBFFF in the current ram bank. Reads past the
+    /// end of the cartridge's RAM return 0xFF.
+    pub fn read_ram(&mut self, address: u16) -> u8 {
+
+        // MBC2's RAM is mirrored across the whole range
+        // and only stores the lower half of each byte
+        if self.banking_type == BankingType::MBC2 {
+            if !self.ram_write_enabled {
+                return 0xFF;
+            }
+            return self.ram_banks[(address & 0x01FF) as usize] | 0xF0;
+        }
+
+        if let Some(register) = self.selected_clock_register {
+            return if self.ram_write_enabled { self.clock.read_register(register) } else { 0xFF };
+        }
+
+        let ram_address = (address - 0xA000) as usize + self.current_ram_bank as usize * 0x2000;
+        match self.ram_banks.get(ram_address) {
+            Some(byte) => *byte,
+            None => 0xFF
+        }
+    }
+
+    /// Sets the byte at an address from 0xA000 to
+    /// 0xBFFF in the current ram bank, if RAM is
+    /// enabled and the address exists.
+    pub fn write_ram(&mut self, address: u16, byte: u8) {
+        if !self.ram_write_enabled {
+            return;
+        }
+
+        if let Some(register) = self.selected_clock_register {
+            self.clock.write_register(register, byte);
+            return;
+        }
+
+        if self.banking_type == BankingType::MBC2 {
+            self.ram_banks[(address & 0x01FF) as usize] = byte & 0x0F;
+            self.ram_dirty = true;
+            return;
+        }
+
+        let ram_address = (address - 0xA000) as usize + self.current_ram_bank as usize * 0x2000;
+        if ram_address < self.ram_banks.len() {
+            self.ram_banks[ram_address] = byte;
+            self.ram_dirty = true;
+        }
     }
 
     /// Getter for the current rom bank.
-    pub fn get_current_rom_bank(&mut self) -> u8 {
+    pub fn get_current_rom_bank(&mut self) -> u16 {
         self.current_rom_bank
     }
 
+    /// Getter for the ROM bank mapped
+    /// to 0x0000-0x3FFF.
+    pub fn get_lo_rom_bank(&mut self) -> u16 {
+        self.lo_rom_bank
+    }
+
     /// Getter for the current ram bank.
     pub fn get_current_ram_bank(&mut self) -> u8 {
         self.current_ram_bank
@@ -0,0 +1,149 @@
+use error::*;
+
+/// The cartridge header, found at
+/// 0x0100-0x014F of every ROM.
+#[derive(Clone, Debug)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb_flag: u8,
+    pub sgb_flag: u8,
+    pub new_licensee_code: [u8; 2],
+    pub old_licensee_code: u8,
+    pub cartridge_type: u8,
+    pub rom_size_code: u8,
+    pub ram_size_code: u8,
+    pub destination_code: u8,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    pub header_checksum_valid: bool,
+    pub global_checksum_valid: bool
+}
+
+impl CartridgeHeader {
+
+    /// Parses the header of a ROM image and
+    /// verifies both of its checksums.
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, EmulatorError> {
+        if rom.len() < 0x150 {
+            return Err(EmulatorError::InvalidRomSize { size: rom.len() });
+        }
+
+        // Newer cartridges take the last 5 bytes of the title
+        // for a manufacturer code and the CGB flag. Only a
+        // code made of capitals and digits is recognised.
+        let cgb_flag = rom[0x143];
+        let code = &rom[0x13F..0x143];
+        let has_manufacturer_code = (cgb_flag & 0x80) != 0 &&
+            code.iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit());
+        let title_end = if has_manufacturer_code {
+            0x13F
+        }
+        else if (cgb_flag & 0x80) != 0 {
+            0x143
+        }
+        else {
+            0x144
+        };
+
+        let header_checksum = CartridgeHeader::compute_header_checksum(rom);
+        let global_checksum = ((rom[0x14E] as u16) << 8) | rom[0x14F] as u16;
+
+        Ok(CartridgeHeader {
+            title: read_text(&rom[0x134..title_end]),
+            manufacturer_code: if has_manufacturer_code { Some(read_text(code)) } else { None },
+            cgb_flag,
+            sgb_flag: rom[0x146],
+            new_licensee_code: [rom[0x144], rom[0x145]],
+            old_licensee_code: rom[0x14B],
+            cartridge_type: rom[0x147],
+            rom_size_code: rom[0x148],
+            ram_size_code: rom[0x149],
+            destination_code: rom[0x14A],
+            version: rom[0x14C],
+            header_checksum: rom[0x14D],
+            global_checksum,
+            header_checksum_valid: header_checksum == rom[0x14D],
+            global_checksum_valid: CartridgeHeader::compute_global_checksum(rom) == global_checksum
+        })
+    }
+
+    /// Computes the checksum of 0x0134-0x014C that the
+    /// boot ROM checks against 0x014D before starting.
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        rom[0x134..0x14D].iter().fold(0u8, |checksum, byte| checksum.wrapping_sub(*byte).wrapping_sub(1))
+    }
+
+    /// Computes the sum of every byte in the ROM except
+    /// the global checksum itself. Hardware never checks it.
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter()
+            .enumerate()
+            .filter(|&(address, _)| address != 0x14E && address != 0x14F)
+            .fold(0u16, |checksum, (_, byte)| checksum.wrapping_add(*byte as u16))
+    }
+
+    /// Returns the ROM size given by 0x148,
+    /// or None for an unknown value.
+    pub fn get_rom_size(&self) -> Option<usize> {
+        match self.rom_size_code {
+            0..=8 => Some(0x8000 << self.rom_size_code),
+            _ => None
+        }
+    }
+
+    /// Returns the size of cartridge RAM given by 0x149.
+    /// This doesn't include RAM built into the MBC.
+    pub fn get_ram_size(&self) -> usize {
+        match self.ram_size_code {
+            1 => 0x800,
+            2 => 0x2000,
+            3 => 0x8000,
+            4 => 0x20000,
+            5 => 0x10000,
+            _ => 0
+        }
+    }
+
+    /// Returns the licensee code, using the two character
+    /// code when the old code is 0x33 and the old code
+    /// in hex otherwise.
+    pub fn get_licensee_code(&self) -> String {
+        if self.old_licensee_code == 0x33 {
+            read_text(&self.new_licensee_code)
+        }
+        else {
+            format!("{:02X}", self.old_licensee_code)
+        }
+    }
+
+    /// Returns whether the cartridge was
+    /// sold only in Japan.
+    pub fn is_japanese(&self) -> bool {
+        self.destination_code == 0x00
+    }
+
+    /// Returns whether the cartridge supports
+    /// Super Game Boy functions.
+    pub fn supports_sgb(&self) -> bool {
+        self.sgb_flag == 0x03
+    }
+
+    /// Returns whether the cartridge only
+    /// runs on a Game Boy Color.
+    pub fn is_cgb_only(&self) -> bool {
+        self.cgb_flag == 0xC0
+    }
+}
+
+/// Converts header bytes to a string, stopping at the
+/// first null and replacing unprintable characters.
+fn read_text(bytes: &[u8]) -> String {
+    bytes.iter()
+        .take_while(|byte| **byte != 0)
+        .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '?' })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
@@ -1,2089 +1,566 @@
 use register_pair::*;
 use memory_manager::*;
 use instructions::*;
+use error::*;
 
-use std::rc::Rc;
-use std::cell::RefCell;
-
-pub struct Cpu {
-
-    // Register pairs
-    reg_af: RegisterPair,
-    reg_bc: RegisterPair,
-    reg_de: RegisterPair,
-    reg_hl: RegisterPair,
-    reg_sp: RegisterPair,
-
-    // Program counter
-    reg_pc: u16,
-
-    // Memory manager
-    memory_manager: Rc<RefCell<MemoryManager>>,
-
-    // Master interrupt switch
-    interrupts_enabled: bool,
-
-    halted: bool,
-}
-
-impl Cpu {
-
-    /// Default constructor.
-    pub fn new(memory_manager: Rc<RefCell<MemoryManager>>) -> Cpu {
-        Cpu {
-            reg_af: RegisterPair::new(0x01B0),
-            reg_bc: RegisterPair::new(0x0013),
-            reg_de: RegisterPair::new(0x00D8),
-            reg_hl: RegisterPair::new(0x014D),
-            reg_sp: RegisterPair::new(0xFFFE),
-            reg_pc: 0x0100,
-            memory_manager: memory_manager,
-            interrupts_enabled: false,
-            halted: false
-        }
-    }
-
-    /// Returns the next byte in memory.
-    pub fn get_byte(&mut self) -> u8 {
-        let byte = self.memory_manager.borrow_mut().read_memory(self.reg_pc);
-        self.reg_pc += 1;
-        byte
-    }
-
-    /// Returns the next word in memory.
-    pub fn get_word(&mut self) -> u16 {
-        let byte_lo = self.memory_manager.borrow_mut().read_memory(self.reg_pc);
-        let byte_hi = self.memory_manager.borrow_mut().read_memory(self.reg_pc + 1);
-        let word = ((byte_hi as u16) << 8) | (byte_lo as u16);
-        self.reg_pc += 2;
-        word
-    }
-
-    /// Getter for the program counter.
-    pub fn get_reg_pc(&mut self) -> u16 {
-        self.reg_pc
-    }
-
-    /// Setter for the program counter.
-    pub fn set_reg_pc(&mut self, reg_pc: u16) {
-        self.reg_pc = reg_pc;
-    } 
-
-    /// Setter for the halted switch.
-    pub fn set_halted(&mut self, halted: bool) {
-        self.halted = halted;
-    }
-
-    /// Getter for the interrupt switch.
-    pub fn get_interrupts_enabled(&mut self) -> bool {
-        self.interrupts_enabled
-    }
-
-    /// Setter for the interrupt switch.
-    pub fn set_interrupts_enabled(&mut self, interrupts_enabled: bool) {
-        self.interrupts_enabled = interrupts_enabled;
-    }
-
-    /// Pushes a word onto the stack.
-    pub fn stack_push(&mut self, val: u16) {
-        let prev = self.reg_sp.get_pair();
-        let val_hi = (val >> 8) as u8;
-        let val_lo = (val & 0xFF) as u8;
-        self.reg_sp.set_pair(prev - 1);
-        self.memory_manager.borrow_mut().write_memory(self.reg_sp.get_pair(), val_hi);
-        self.reg_sp.set_pair(prev - 2);
-        self.memory_manager.borrow_mut().write_memory(self.reg_sp.get_pair(), val_lo);
-    }
-
-    /// Pops a word off the stack.
-    pub fn stack_pop(&mut self) -> u16 {
-        let prev = self.reg_sp.get_pair();
-        let mut word = (self.memory_manager.borrow_mut().read_memory(self.reg_sp.get_pair() + 1) as u16) << 8;
-        word |= self.memory_manager.borrow_mut().read_memory(self.reg_sp.get_pair()) as u16;
-        self.reg_sp.set_pair(prev + 2);
-        word
-    } 
-
-    /// Calls a subroutine at a given address.
-    pub fn call_routine(&mut self, address: u16) {
-        let pc = self.reg_pc;
-        self.stack_push(pc);
-        self.reg_pc = address;
-    }
-
-    /// Updates the bit for the zero flag.
-    pub fn update_zero_flag(&mut self, result: bool) {
-        if result {
-            set_bit(&mut self.reg_af.lo, 7);
-        }
-        else {
-            reset_bit(&mut self.reg_af.lo, 7);
-        }
-    }
-
-    /// Updates the bit for the subtract flag.
-    pub fn update_subtract_flag(&mut self, sub_occurred: bool) {
-        if sub_occurred {
-            set_bit(&mut self.reg_af.lo, 6);
-        }
-        else {
-            reset_bit(&mut self.reg_af.lo, 6);
-        }
-    }
-
-    /// Updates the bit for the half carry flag.
-    pub fn update_half_carry_flag(&mut self, half_carry_occurred: bool) {
-        if half_carry_occurred {
-            set_bit(&mut self.reg_af.lo, 5);
-        }
-        else {
-            reset_bit(&mut self.reg_af.lo, 5);
-        }
-    }
-
-    /// Updates the bit for the carry flag.
-    pub fn update_carry_flag(&mut self, carry_occurred: bool) {
-        if carry_occurred {
-            set_bit(&mut self.reg_af.lo, 4);
-        }
-        else {
-            reset_bit(&mut self.reg_af.lo, 4);
-        }
-    }
-
-    /// Moves the PC and executes the next opcode,
-    /// then returns the number of cycles it 
-    /// took.
-    pub fn interpret_opcode(&mut self) -> i32 {
-
-        // Don't run if halted
-        if self.halted {
-            return 4;
-        }
-
-        // println!("BC:\t0x{:04X}\nDE:\t0x{:04X}\nHL:\t0x{:04X}\nAF:\t0x{:04X}\nSP:\t0x{:04X}\nPC:\t0x{:04X}\n", self.reg_bc.get_pair(), self.reg_de.get_pair(), self.reg_hl.get_pair(), self.reg_af.get_pair(), self.reg_sp.get_pair(), self.reg_pc);
-        let opcode = self.memory_manager.borrow_mut().read_memory(self.reg_pc);
-        self.reg_pc += 1;
-
-        // println!("Opcode: 0x{:02X}", opcode);
-        // println!("{:04X}", self.reg_pc);
-        match opcode {
-            0x00 => { /* NOP */ 4 },
-            0x01 => { ld_u16_reg_pair(self.get_word(), &mut self.reg_bc); 12 },
-            0x02 => { self.memory_manager.borrow_mut().write_memory(self.reg_bc.get_pair(), self.reg_af.hi); 8 },
-            0x03 => { inc_reg_pair(&mut self.reg_bc); 8 },
-            0x04 => { 
-                let mut b = self.reg_bc.hi;
-                self.inc_u8(&mut b);
-                self.reg_bc.hi = b;
-                4
-            },
-            0x05 => { 
-                let mut b = self.reg_bc.hi;
-                self.dec_u8(&mut b);
-                self.reg_bc.hi = b;
-                4
-            },
-            0x06 => { ld_u8_reg(self.get_byte(), &mut self.reg_bc.hi); 8 },
-            0x07 => {
-                let mut a = self.reg_af.hi;
-                self.rlc_u8(&mut a);
-                self.reg_af.hi = a;
-                self.update_zero_flag(false);
-                4
-            },
-            0x08 => { 
-                let address = self.get_word();
-                self.memory_manager.borrow_mut().write_memory(address, self.reg_sp.lo);
-                self.memory_manager.borrow_mut().write_memory(address + 1, self.reg_sp.hi);
-                20
-            },
-            0x09 => {
-                let mut bc = self.reg_bc.get_pair();
-                self.add_u16_hl(bc);
-                8
-            },
-            0x0A => { self.reg_af.hi = self.memory_manager.borrow_mut().read_memory(self.reg_bc.get_pair()); 8 },
-            0x0B => { 
-                let val = self.reg_bc.get_pair();
-                self.reg_bc.set_pair(val.wrapping_sub(1));
-                8
-            },
-            0x0C => { 
-                let mut c = self.reg_bc.lo;
-                self.inc_u8(&mut c);
-                self.reg_bc.lo = c;
-                4
-            },
-            0x0D => { 
-                let mut c = self.reg_bc.lo;
-                self.dec_u8(&mut c);
-                self.reg_bc.lo = c;
-                4
-            },
-            0x0E => { ld_u8_reg(self.get_byte(), &mut self.reg_bc.lo); 8 },
-            0x0F => {
-                let mut a = self.reg_af.hi;
-                self.rrc_u8(&mut a);
-                self.reg_af.hi = a;
-                self.update_zero_flag(false);
-                4
-            },
-            0x10 => { 4 },
-            0x11 => { ld_u16_reg_pair(self.get_word(), &mut self.reg_de); 12 },
-            0x12 => { self.memory_manager.borrow_mut().write_memory(self.reg_de.get_pair(), self.reg_af.hi); 8 },
-            0x13 => { inc_reg_pair(&mut self.reg_de); 8 },
-            0x14 => { 
-                let mut d = self.reg_de.hi;
-                self.inc_u8(&mut d);
-                self.reg_de.hi = d;
-                4
-            },
-            0x15 => { 
-                let mut d = self.reg_de.hi;
-                self.dec_u8(&mut d);
-                self.reg_de.hi = d;
-                4
-            },
-            0x16 => { ld_u8_reg(self.get_byte(), &mut self.reg_de.hi); 8 },
-            0x17 => {
-                let mut val = self.reg_af.hi;
-                self.rl_u8(&mut val);
-                self.reg_af.hi = val;
-                self.update_zero_flag(false);
-                4
-            },
-            0x18 => { self.reg_pc = ((self.get_byte() as i8) as i32 + ((self.reg_pc as u32) as i32)) as u16; 12 },
-            0x19 => {
-                let mut de = self.reg_de.get_pair();
-                self.add_u16_hl(de);
-                8
-            },
-            0x1A => { self.reg_af.hi = self.memory_manager.borrow_mut().read_memory(self.reg_de.get_pair()); 8 },
-            0x1B => { 
-                let val = self.reg_de.get_pair();
-                self.reg_de.set_pair(val.wrapping_sub(1));
-                8
-            },
-            0x1C => { 
-                let mut e = self.reg_de.lo;
-                self.inc_u8(&mut e);
-                self.reg_de.lo = e;
-                4
-            },
-            0x1D => { 
-                let mut e = self.reg_de.lo;
-                self.dec_u8(&mut e);
-                self.reg_de.lo = e;
-                4
-            },
-            0x1E => { ld_u8_reg(self.get_byte(), &mut self.reg_de.lo); 8 },
-            0x1F => {
-                let mut a = self.reg_af.hi;
-                self.rr_u8(&mut a);
-                self.reg_af.hi = a;
-                self.update_zero_flag(false);
-                4
-            },
-            0x20 => {
-                if !test_bit(self.reg_af.lo, 7) {
-                    self.reg_pc = ((self.get_byte() as i8) as i32 + ((self.reg_pc as u32) as i32)) as u16;
-                    12
-                }
-                else {
-                    self.reg_pc += 1;
-                    8
-                }
-            },
-            0x21 => { ld_u16_reg_pair(self.get_word(), &mut self.reg_hl); 12 },
-            0x22 => {
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_af.hi);
-                inc_reg_pair(&mut self.reg_hl);
-                8
-            },
-            0x23 => { inc_reg_pair(&mut self.reg_hl); 8 },
-            0x24 => { 
-                let mut h = self.reg_hl.hi;
-                self.inc_u8(&mut h);
-                self.reg_hl.hi = h;
-                4
-            },
-            0x25 => { 
-                let mut h = self.reg_hl.hi;
-                self.dec_u8(&mut h);
-                self.reg_hl.hi = h;
-                4
-            },
-            0x26 => { ld_u8_reg(self.get_byte(), &mut self.reg_hl.hi); 8 },
-            0x27 => { self.daa(); 4 },
-            0x28 => {
-                if test_bit(self.reg_af.lo, 7) {
-                    self.reg_pc = ((self.get_byte() as i8) as i32 + ((self.reg_pc as u32) as i32)) as u16;
-                    12
-                }
-                else {
-                    self.reg_pc += 1;
-                    8
-                }
-            },
-            0x29 => {
-                let mut hl = self.reg_hl.get_pair();
-                self.add_u16_hl(hl);
-                8
-            },
-            0x2A => {
-                self.reg_af.hi = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                inc_reg_pair(&mut self.reg_hl);
-                8
-            },
-            0x2B => { 
-                let val = self.reg_hl.get_pair();
-                self.reg_hl.set_pair(val.wrapping_sub(1));
-                8
-            },
-            0x2C => { 
-                let mut l = self.reg_hl.lo;
-                self.inc_u8(&mut l);
-                self.reg_hl.lo = l;
-                4
-            },
-            0x2D => { 
-                let mut l = self.reg_hl.lo;
-                self.dec_u8(&mut l);
-                self.reg_hl.lo = l;
-                4
-            },
-            0x2E => { ld_u8_reg(self.get_byte(), &mut self.reg_hl.lo); 8 },
-            0x2F => { 
-                self.reg_af.hi = !self.reg_af.hi;
-                self.update_subtract_flag(true);
-                self.update_half_carry_flag(true);
-                4
-            },
-            0x30 => {
-                if !test_bit(self.reg_af.lo, 4) {
-                    self.reg_pc = ((self.get_byte() as i8) as i32 + ((self.reg_pc as u32) as i32)) as u16;
-                    12
-                }
-                else {
-                    self.reg_pc += 1;
-                    8
-                }
-            },
-            0x31 => { ld_u16_reg_pair(self.get_word(), &mut self.reg_sp); 12 },
-            0x32 => {
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_af.hi);
-                dec_reg_pair(&mut self.reg_hl);
-                8
-            },
-            0x33 => { inc_reg_pair(&mut self.reg_sp); 8 },
-            0x34 => {
-                let mut byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.inc_u8(&mut byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), byte);
-                12
-            },
-            0x35 => {
-                let mut byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.dec_u8(&mut byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), byte);
-                12
-            },
-            0x36 => { 
-                let byte = self.get_byte();
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), byte);
-                12
-            },
-            0x37 => {
-                self.update_carry_flag(true);
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(false);
-                4
-            },
-            0x38 => {
-                if test_bit(self.reg_af.lo, 4) {
-                    self.reg_pc = ((self.get_byte() as i8) as i32 + ((self.reg_pc as u32) as i32)) as u16;
-                    12
-                }
-                else {
-                    self.reg_pc += 1;
-                    8
-                }
-            },
-            0x39 => {
-                let mut sp = self.reg_sp.get_pair();
-                self.add_u16_hl(sp);
-                8
-            },
-            0x3A => {
-                self.reg_af.hi = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                dec_reg_pair(&mut self.reg_hl);
-                8
-            },
-            0x3B => { 
-                let val = self.reg_sp.get_pair().wrapping_sub(1);
-                self.reg_sp.set_pair(val);
-                8
-            },
-            0x3C => { 
-                let mut a = self.reg_af.hi;
-                self.inc_u8(&mut a);
-                self.reg_af.hi = a;
-                4
-            },
-            0x3D => { 
-                let mut a = self.reg_af.hi;
-                self.dec_u8(&mut a);
-                self.reg_af.hi = a;
-                4
-            },
-            0x3E => { ld_u8_reg(self.get_byte(), &mut self.reg_af.hi); 8 },
-            0x3F => {
-                let carry_set = test_bit(self.reg_af.lo, 4);
-                self.update_carry_flag(!carry_set);
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(false);
-                4
-            },
-            0x40 => { /* LD B, B */ 4 },
-            0x41 => { ld_u8_reg(self.reg_bc.lo, &mut self.reg_bc.hi); 4 },
-            0x42 => { ld_u8_reg(self.reg_de.hi, &mut self.reg_bc.hi); 4 },
-            0x43 => { ld_u8_reg(self.reg_de.lo, &mut self.reg_bc.hi); 4 },
-            0x44 => { ld_u8_reg(self.reg_hl.hi, &mut self.reg_bc.hi); 4 },
-            0x45 => { ld_u8_reg(self.reg_hl.lo, &mut self.reg_bc.hi); 4 },
-            0x46 => { ld_u8_reg(self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair()), &mut self.reg_bc.hi); 8 },
-            0x47 => { ld_u8_reg(self.reg_af.hi, &mut self.reg_bc.hi); 4 },
-            0x48 => { ld_u8_reg(self.reg_bc.hi, &mut self.reg_bc.lo); 4 },
-            0x49 => { /* LD C, C */ 4 },
-            0x4A => { ld_u8_reg(self.reg_de.hi, &mut self.reg_bc.lo); 4 },
-            0x4B => { ld_u8_reg(self.reg_de.lo, &mut self.reg_bc.lo); 4 },
-            0x4C => { ld_u8_reg(self.reg_hl.hi, &mut self.reg_bc.lo); 4 },
-            0x4D => { ld_u8_reg(self.reg_hl.lo, &mut self.reg_bc.lo); 4 },
-            0x4E => { ld_u8_reg(self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair()), &mut self.reg_bc.lo); 4 },
-            0x4F => { ld_u8_reg(self.reg_af.hi, &mut self.reg_bc.lo); 4 },
-            0x50 => { ld_u8_reg(self.reg_bc.hi, &mut self.reg_de.hi); 4 },
-            0x51 => { ld_u8_reg(self.reg_bc.lo, &mut self.reg_de.hi); 4 },
-            0x52 => { /* LD D, D */ 4 },
-            0x53 => { ld_u8_reg(self.reg_de.lo, &mut self.reg_de.hi); 4 },
-            0x54 => { ld_u8_reg(self.reg_hl.hi, &mut self.reg_de.hi); 4 },
-            0x55 => { ld_u8_reg(self.reg_hl.lo, &mut self.reg_de.hi); 4 },
-            0x56 => { ld_u8_reg(self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair()), &mut self.reg_de.hi); 8 },
-            0x57 => { ld_u8_reg(self.reg_af.hi, &mut self.reg_de.hi); 4 },
-            0x58 => { ld_u8_reg(self.reg_bc.hi, &mut self.reg_de.lo); 4 },
-            0x59 => { ld_u8_reg(self.reg_bc.lo, &mut self.reg_de.lo); 4 },
-            0x5A => { ld_u8_reg(self.reg_de.hi, &mut self.reg_de.lo); 4 },
-            0x5B => { /* LD E, E */ 4 },
-            0x5C => { ld_u8_reg(self.reg_hl.hi, &mut self.reg_de.lo); 4 },
-            0x5D => { ld_u8_reg(self.reg_hl.lo, &mut self.reg_de.lo); 4 },
-            0x5E => { ld_u8_reg(self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair()), &mut self.reg_de.lo); 8 },
-            0x5F => { ld_u8_reg(self.reg_af.hi, &mut self.reg_de.lo); 4 },
-            0x60 => { ld_u8_reg(self.reg_bc.hi, &mut self.reg_hl.hi); 4 },
-            0x61 => { ld_u8_reg(self.reg_bc.lo, &mut self.reg_hl.hi); 4 },
-            0x62 => { ld_u8_reg(self.reg_de.hi, &mut self.reg_hl.hi); 4 },
-            0x63 => { ld_u8_reg(self.reg_de.lo, &mut self.reg_hl.hi); 4 },
-            0x64 => { /* LD H, H */ 4 },
-            0x65 => { ld_u8_reg(self.reg_hl.lo, &mut self.reg_hl.hi); 4 },
-            0x66 => { ld_u8_reg(self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair()), &mut self.reg_hl.hi); 8 },
-            0x67 => { ld_u8_reg(self.reg_af.hi, &mut self.reg_hl.hi); 4 },
-            0x68 => { ld_u8_reg(self.reg_bc.hi, &mut self.reg_hl.lo); 4 },
-            0x69 => { ld_u8_reg(self.reg_bc.lo, &mut self.reg_hl.lo); 4 },
-            0x6A => { ld_u8_reg(self.reg_de.hi, &mut self.reg_hl.lo); 4 },
-            0x6B => { ld_u8_reg(self.reg_de.lo, &mut self.reg_hl.lo); 4 },
-            0x6C => { ld_u8_reg(self.reg_hl.hi, &mut self.reg_hl.lo); 4 },
-            0x6D => { /* LD L, L */ 4 },
-            0x6E => { ld_u8_reg(self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair()), &mut self.reg_hl.lo); 8 },
-            0x6F => { ld_u8_reg(self.reg_af.hi, &mut self.reg_hl.lo); 4 },
-            0x70 => { self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_bc.hi); 8 },
-            0x71 => { self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_bc.lo); 8 },
-            0x72 => { self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_de.hi); 8 },
-            0x73 => { self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_de.lo); 8 },
-            0x74 => { self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_hl.hi); 8 },
-            0x75 => { self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_hl.lo); 8 },
-            0x76 => { self.halted = true; 4 },
-            0x77 => { self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), self.reg_af.hi); 8 },
-            0x78 => { ld_u8_reg(self.reg_bc.hi, &mut self.reg_af.hi); 4 },
-            0x79 => { ld_u8_reg(self.reg_bc.lo, &mut self.reg_af.hi); 4 },
-            0x7A => { ld_u8_reg(self.reg_de.hi, &mut self.reg_af.hi); 4 },
-            0x7B => { ld_u8_reg(self.reg_de.lo, &mut self.reg_af.hi); 4 },
-            0x7C => { ld_u8_reg(self.reg_hl.hi, &mut self.reg_af.hi); 4 },
-            0x7D => { ld_u8_reg(self.reg_hl.lo, &mut self.reg_af.hi); 4 },
-            0x7E => { ld_u8_reg(self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair()), &mut self.reg_af.hi); 8 },
-            0x7F => { /* LD A, A */ 4 },
-            0x80 => {
-                let val = self.reg_bc.hi;
-                self.add_u8_a(val);
-                4
-            },
-            0x81 => {
-                let val = self.reg_bc.lo;
-                self.add_u8_a(val);
-                4
-            },
-            0x82 => {
-                let val = self.reg_de.hi;
-                self.add_u8_a(val);
-                4
-            },
-            0x83 => {
-                let val = self.reg_de.lo;
-                self.add_u8_a(val);
-                4
-            },
-            0x84 => {
-                let val = self.reg_hl.hi;
-                self.add_u8_a(val);
-                4
-            },
-            0x85 => {
-                let val = self.reg_hl.lo;
-                self.add_u8_a(val);
-                4
-            },
-            0x86 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.add_u8_a(val);
-                8
-            },
-            0x87 => {
-                let val = self.reg_af.hi;
-                self.add_u8_a(val);
-                4
-            },
-            0x88 => {
-                let val = self.reg_bc.hi;
-                self.adc_reg_a(val);
-                4
-            },
-            0x89 => {
-                let val = self.reg_bc.lo;
-                self.adc_reg_a(val);
-                4
-            },
-            0x8A => {
-                let val = self.reg_de.hi;
-                self.adc_reg_a(val);
-                4
-            },
-            0x8B => {
-                let val = self.reg_de.lo;
-                self.adc_reg_a(val);
-                4
-            },
-            0x8C => {
-                let val = self.reg_hl.hi;
-                self.adc_reg_a(val);
-                4
-            },
-            0x8D => {
-                let val = self.reg_hl.lo;
-                self.adc_reg_a(val);
-                4
-            },
-            0x8E => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.adc_reg_a(val);
-                8
-            },
-            0x8F => {
-                let val = self.reg_af.hi;
-                self.adc_reg_a(val);
-                4
-            },
-            0x90 => {
-                let val = self.reg_bc.hi;
-                self.sub_u8_a(val);
-                4
-            },
-            0x91 => {
-                let val = self.reg_bc.lo;
-                self.sub_u8_a(val);
-                4
-            },
-            0x92 => {
-                let val = self.reg_de.hi;
-                self.sub_u8_a(val);
-                4
-            },
-            0x93 => {
-                let val = self.reg_de.lo;
-                self.sub_u8_a(val);
-                4
-            },
-            0x94 => {
-                let val = self.reg_hl.hi;
-                self.sub_u8_a(val);
-                4
-            },
-            0x95 => {
-                let val = self.reg_hl.lo;
-                self.sub_u8_a(val);
-                4
-            },
-            0x96 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.sub_u8_a(val);
-                8
-            },
-            0x97 => {
-                let val = self.reg_af.hi;
-                self.sub_u8_a(val);
-                4
-            }
-            0x98 => {
-                let val = self.reg_bc.hi;
-                self.sbc_reg_a(val);
-                4
-            }
-            0x99 => {
-                let val = self.reg_bc.lo;
-                self.sbc_reg_a(val);
-                4
-            }
-            0x9A => {
-                let val = self.reg_de.hi;
-                self.sbc_reg_a(val);
-                4
-            }
-            0x9B => {
-                let val = self.reg_de.lo;
-                self.sbc_reg_a(val);
-                4
-            }
-            0x9C => {
-                let val = self.reg_hl.hi;
-                self.sbc_reg_a(val);
-                4
-            }
-            0x9D => {
-                let val = self.reg_hl.lo;
-                self.sbc_reg_a(val);
-                4
-            }
-            0x9E => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.sbc_reg_a(val);
-                8
-            },
-            0x9F => {
-                let val = self.reg_af.hi;
-                self.sbc_reg_a(val);
-                4
-            }
-            0xA0 => {
-                let val = self.reg_bc.hi;
-                self.and_reg_a(val);
-                4
-            },
-            0xA1 => {
-                let val = self.reg_bc.lo;
-                self.and_reg_a(val);
-                4
-            },
-            0xA2 => {
-                let val = self.reg_de.hi;
-                self.and_reg_a(val);
-                4
-            },
-            0xA3 => {
-                let val = self.reg_de.lo;
-                self.and_reg_a(val);
-                4
-            },
-            0xA4 => {
-                let val = self.reg_hl.hi;
-                self.and_reg_a(val);
-                4
-            },
-            0xA5 => {
-                let val = self.reg_hl.lo;
-                self.and_reg_a(val);
-                4
-            },
-            0xA6 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.and_reg_a(val);
-                8
-            },
-            0xA7 => {
-                let val = self.reg_af.hi;
-                self.and_reg_a(val);
-                4
-            },
-            0xA8 => {
-                let val = self.reg_bc.hi;
-                self.xor_reg_a(val);
-                4
-            },
-            0xA9 => {
-                let val = self.reg_bc.lo;
-                self.xor_reg_a(val);
-                4
-            },
-            0xAA => {
-                let val = self.reg_de.hi;
-                self.xor_reg_a(val);
-                4
-            },
-            0xAB => {
-                let val = self.reg_de.lo;
-                self.xor_reg_a(val);
-                4
-            },
-            0xAC => {
-                let val = self.reg_hl.hi;
-                self.xor_reg_a(val);
-                4
-            },
-            0xAD => {
-                let val = self.reg_hl.lo;
-                self.xor_reg_a(val);
-                4
-            },
-            0xAE => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.xor_reg_a(val);
-                8
-            },
-            0xAF => {
-                let val = self.reg_af.hi;
-                self.xor_reg_a(val);
-                4
-            },
-            0xB0 => {
-                let val = self.reg_bc.hi;
-                self.or_reg_a(val);
-                4
-            },
-            0xB1 => {
-                let val = self.reg_bc.lo;
-                self.or_reg_a(val);
-                4
-            },
-            0xB2 => {
-                let val = self.reg_de.hi;
-                self.or_reg_a(val);
-                4
-            },
-            0xB3 => {
-                let val = self.reg_de.lo;
-                self.or_reg_a(val);
-                4
-            },
-            0xB4 => {
-                let val = self.reg_hl.hi;
-                self.or_reg_a(val);
-                4
-            },
-            0xB5 => {
-                let val = self.reg_hl.lo;
-                self.or_reg_a(val);
-                4
-            },
-            0xB6 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.or_reg_a(val);
-                8
-            },
-            0xB7 => {
-                let val = self.reg_af.hi;
-                self.or_reg_a(val);
-                4
-            },
-            0xB8 => {
-                let val = self.reg_bc.hi;
-                self.cp_reg_a(val);
-                4
-            },
-            0xB9 => {
-                let val = self.reg_bc.lo;
-                self.cp_reg_a(val);
-                4
-            },
-            0xBA => {
-                let val = self.reg_de.hi;
-                self.cp_reg_a(val);
-                4
-            },
-            0xBB => {
-                let val = self.reg_de.lo;
-                self.cp_reg_a(val);
-                4
-            },
-            0xBC => {
-                let val = self.reg_hl.hi;
-                self.cp_reg_a(val);
-                4
-            },
-            0xBD => {
-                let val = self.reg_hl.lo;
-                self.cp_reg_a(val);
-                4
-            },
-            0xBE => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.cp_reg_a(val);
-                8
-            },
-            0xBF => {
-                let val = self.reg_af.hi;
-                self.cp_reg_a(val);
-                4
-            },
-            0xC0 => {
-                if !test_bit(self.reg_af.lo, 7) {
-                    self.reg_pc = self.stack_pop();
-                    20
-                }
-                else {
-                    8
-                }
-            },
-            0xC1 => { 
-                let val = self.stack_pop();
-                self.reg_bc.set_pair(val);
-                12
-            },
-            0xC2 => {
-                if !test_bit(self.reg_af.lo, 7) {
-                    self.reg_pc = self.get_word();
-                    16
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xC3 => { self.reg_pc = self.get_word(); 16 },
-            0xC4 => {
-                if !test_bit(self.reg_af.lo, 7) {
-                    let pc = self.reg_pc;
-                    self.stack_push(pc + 2);
-                    self.reg_pc = self.get_word();
-                    24
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xC5 => { 
-                let val = self.reg_bc.get_pair();
-                self.stack_push(val);
-                16
-            },
-            0xC6 => {
-                let val = self.get_byte();
-                self.add_u8_a(val);
-                8
-            },
-            0xC7 => { self.call_routine(0x0000); 16 },
-            0xC8 => {
-                if test_bit(self.reg_af.lo, 7) {
-                    self.reg_pc = self.stack_pop();
-                    20
-                }
-                else {
-                    8 
-                }
-            },
-            0xC9 => { self.reg_pc = self.stack_pop(); 16 },
-            0xCA => {
-                if test_bit(self.reg_af.lo, 7) {
-                    self.reg_pc = self.get_word();
-                    16
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xCB => { self.extended_instruction() },
-            0xCC => {
-                if test_bit(self.reg_af.lo, 7) {
-                    let pc = self.reg_pc;
-                    self.stack_push(pc + 2);
-                    self.reg_pc = self.get_word();
-                    24
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xCD => {
-                let pc = self.reg_pc;
-                self.stack_push(pc + 2);
-                self.reg_pc = self.get_word();
-                24
-            },
-            0xCE => {
-                let val = self.get_byte();
-                self.adc_reg_a(val);
-                8
-            },
-            0xCF => { self.call_routine(0x0008); 16 },
-            0xD0 => {
-                if !test_bit(self.reg_af.lo, 4) {
-                    self.reg_pc = self.stack_pop();
-                    20
-                }
-                else {
-                    8
-                }
-            },
-            0xD1 => {
-                let val = self.stack_pop();
-                self.reg_de.set_pair(val);
-                12
-            },
-            0xD2 => {
-                if !test_bit(self.reg_af.lo, 4) {
-                    self.reg_pc = self.get_word();
-                    16
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xD4 => {
-                if !test_bit(self.reg_af.lo, 4) {
-                    let pc = self.reg_pc;
-                    self.stack_push(pc + 2);
-                    self.reg_pc = self.get_word();
-                    24
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xD5 => {
-                let val = self.reg_de.get_pair();
-                self.stack_push(val);
-                16
-            },
-            0xD6 => {
-                let val = self.get_byte();
-                self.sub_u8_a(val);
-                8
-            }
-            0xD7 => { self.call_routine(0x0010); 16 },
-            0xD8 => {
-                if test_bit(self.reg_af.lo, 4) {
-                    self.reg_pc = self.stack_pop();
-                    20
-                }
-                else {
-                    8
-                }
-            },
-            0xD9 => { 
-                self.interrupts_enabled = true;
-                self.reg_pc = self.stack_pop();
-                16
-            },
-            0xDA => {
-                if test_bit(self.reg_af.lo, 4) {
-                    self.reg_pc = self.get_word();
-                    16
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xDC => {
-                if test_bit(self.reg_af.lo, 4) {
-                    let pc = self.reg_pc;
-                    self.stack_push(pc + 2);
-                    self.reg_pc = self.get_word();
-                    24
-                }
-                else {
-                    self.reg_pc += 2;
-                    12
-                }
-            },
-            0xDE => {
-                let val = self.get_byte();
-                self.sbc_reg_a(val);
-                8
-            }
-            0xDF => { self.call_routine(0x0018); 16 },
-            0xE0 => { 
-                let address = self.get_byte() as u16 | 0xFF00;
-                self.memory_manager.borrow_mut().write_memory(address, self.reg_af.hi);
-                12
-            },
-            0xE1 => {
-                let val = self.stack_pop();
-                self.reg_hl.set_pair(val);
-                12
-            },
-            0xE2 => { 
-                let address = self.reg_bc.lo as u16 | 0xFF00;
-                self.memory_manager.borrow_mut().write_memory(address, self.reg_af.hi);
-                8
-            },
-            0xE5 => {
-                let val = self.reg_hl.get_pair();
-                self.stack_push(val);
-                16
-            },
-            0xE6 => {
-                let val = self.get_byte();
-                self.and_reg_a(val);
-                8
-            }
-            0xE7 => { self.call_routine(0x0020); 16 },
-            0xE8 => {
-                let byte = self.get_byte() as i8 as i16 as u16;
-                let sp = self.reg_sp.get_pair();
-                self.reg_sp.set_pair(sp.wrapping_add(byte));
-                self.update_half_carry_flag((byte & 0x000F) + (sp & 0x000F) > 0x000F);
-                self.update_carry_flag((byte & 0x00FF) + (sp & 0x00FF) > 0x00FF);
-                self.update_zero_flag(false);
-                self.update_subtract_flag(false);
-                16
-            },
-            0xE9 => { self.reg_pc = self.reg_hl.get_pair(); 4 },
-            0xEA => { 
-                let address = self.get_word();
-                self.memory_manager.borrow_mut().write_memory(address, self.reg_af.hi);
-                16
-            },
-            0xEE => {
-                let val = self.get_byte();
-                self.xor_reg_a(val);
-                8
-            }
-            0xEF => { self.call_routine(0x0028); 16 },
-            0xF0 => { 
-                let address = self.get_byte() as u16 | 0xFF00;
-                self.reg_af.hi = self.memory_manager.borrow_mut().read_memory(address);
-                12
-            },
-            0xF1 => {
-                let val = self.stack_pop() & 0xFFF0;
-                self.reg_af.set_pair(val);
-                12
-            },
-            0xF2 => { 
-                let address = self.reg_bc.lo as u16 | 0xFF00;
-                self.reg_af.hi = self.memory_manager.borrow_mut().read_memory(address);
-                8
-            },
-            0xF3 => { self.interrupts_enabled = false; 4 },
-            0xF5 => {
-                let val = self.reg_af.get_pair();
-                self.stack_push(val);
-                16
-            },
-            0xF6 => {
-                let val = self.get_byte();
-                self.or_reg_a(val);
-                8
-            }
-            0xF7 => { self.call_routine(0x0030); 16 },
-            0xF8 => {
-                let byte = self.get_byte() as i8 as i16 as u16;
-                let sp = self.reg_sp.get_pair();
-                self.reg_hl.set_pair(sp.wrapping_add(byte));
-                self.update_half_carry_flag((byte & 0x000F) + (sp & 0x000F) > 0x000F);
-                self.update_carry_flag((byte & 0x00FF) + (sp & 0x00FF) > 0x00FF);
-                self.update_zero_flag(false);
-                self.update_subtract_flag(false);
-                12
-            },
-            0xF9 => { self.reg_sp.set_pair(self.reg_hl.get_pair()); 8 },
-            0xFA => { 
-                let address = self.get_word();
-                self.reg_af.hi = self.memory_manager.borrow_mut().read_memory(address);
-                16
-            },
-            0xFB => { self.interrupts_enabled = true; 4 },
-            0xFE => {
-                let val = self.get_byte();
-                self.cp_reg_a(val);
-                8
-            }
-            0xFF => { self.call_routine(0x0038); 16 },
-            _ => panic!("Undefined opcode: 0x{:02X}", opcode)
-        }
-    }
-
-    /// Executes an extended instruction.
-    pub fn extended_instruction(&mut self) -> i32 {
-        let opcode = self.get_byte();
-        match opcode {
-            0x00 => { 
-                let mut b = self.reg_bc.hi;
-                self.rlc_u8(&mut b);
-                self.reg_bc.hi = b;
-                8
-            },
-            0x01 => { 
-                let mut c = self.reg_bc.lo;
-                self.rlc_u8(&mut c);
-                self.reg_bc.lo = c;
-                8
-            },
-            0x02 => { 
-                let mut d = self.reg_de.hi;
-                self.rlc_u8(&mut d);
-                self.reg_de.hi = d;
-                8
-            },
-            0x03 => { 
-                let mut e = self.reg_de.lo;
-                self.rlc_u8(&mut e);
-                self.reg_de.lo = e;
-                8
-            },
-            0x04 => { 
-                let mut h = self.reg_hl.hi;
-                self.rlc_u8(&mut h);
-                self.reg_hl.hi = h;
-                8
-            },
-            0x05 => { 
-                let mut l = self.reg_hl.lo;
-                self.rlc_u8(&mut l);
-                self.reg_hl.lo = l;
-                8
-            },
-            0x06 => {
-                let mut byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.rlc_u8(&mut byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), byte);
-                16
-            },
-            0x07 => { 
-                let mut a = self.reg_af.hi;
-                self.rlc_u8(&mut a);
-                self.reg_af.hi = a;
-                8
-            },
-            0x08 => { 
-                let mut b = self.reg_bc.hi;
-                self.rrc_u8(&mut b);
-                self.reg_bc.hi = b;
-                8
-            },
-            0x09 => { 
-                let mut c = self.reg_bc.lo;
-                self.rrc_u8(&mut c);
-                self.reg_bc.lo = c;
-                8
-            },
-            0x0A => { 
-                let mut d = self.reg_de.hi;
-                self.rrc_u8(&mut d);
-                self.reg_de.hi = d;
-                8
-            },
-            0x0B => { 
-                let mut e = self.reg_de.lo;
-                self.rrc_u8(&mut e);
-                self.reg_de.lo = e;
-                8
-            },
-            0x0C => { 
-                let mut h = self.reg_hl.hi;
-                self.rrc_u8(&mut h);
-                self.reg_hl.hi = h;
-                8
-            },
-            0x0D => { 
-                let mut l = self.reg_hl.lo;
-                self.rrc_u8(&mut l);
-                self.reg_hl.lo = l;
-                8
-            },
-            0x0E => {
-                let mut byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.rrc_u8(&mut byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), byte);
-                16
-            },
-            0x0F => { 
-                let mut a = self.reg_af.hi;
-                self.rrc_u8(&mut a);
-                self.reg_af.hi = a;
-                8
-            },
-            0x10 => { 
-                let mut b = self.reg_bc.hi;
-                self.rl_u8(&mut b);
-                self.reg_bc.hi = b;
-                8
-            },
-            0x11 => { 
-                let mut c = self.reg_bc.lo;
-                self.rl_u8(&mut c);
-                self.reg_bc.lo = c;
-                8
-            },
-            0x12 => { 
-                let mut d = self.reg_de.hi;
-                self.rl_u8(&mut d);
-                self.reg_de.hi = d;
-                8
-            },
-            0x13 => { 
-                let mut e = self.reg_de.lo;
-                self.rl_u8(&mut e);
-                self.reg_de.lo = e;
-                8
-            },
-            0x14 => { 
-                let mut h = self.reg_hl.hi;
-                self.rl_u8(&mut h);
-                self.reg_hl.hi = h;
-                8
-            },
-            0x15 => { 
-                let mut l = self.reg_hl.lo;
-                self.rl_u8(&mut l);
-                self.reg_hl.lo = l;
-                8
-            },
-            0x16 => {
-                let mut byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.rl_u8(&mut byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), byte);
-                16
-            },
-            0x17 => { 
-                let mut a = self.reg_af.hi;
-                self.rl_u8(&mut a);
-                self.reg_af.hi = a;
-                8
-            },
-            0x18 => { 
-                let mut b = self.reg_bc.hi;
-                self.rr_u8(&mut b);
-                self.reg_bc.hi = b;
-                8
-            },
-            0x19 => { 
-                let mut c = self.reg_bc.lo;
-                self.rr_u8(&mut c);
-                self.reg_bc.lo = c;
-                8
-            },
-            0x1A => { 
-                let mut d = self.reg_de.hi;
-                self.rr_u8(&mut d);
-                self.reg_de.hi = d;
-                8
-            },
-            0x1B => { 
-                let mut e = self.reg_de.lo;
-                self.rr_u8(&mut e);
-                self.reg_de.lo = e;
-                8
-            },
-            0x1C => { 
-                let mut h = self.reg_hl.hi;
-                self.rr_u8(&mut h);
-                self.reg_hl.hi = h;
-                8
-            },
-            0x1D => { 
-                let mut l = self.reg_hl.lo;
-                self.rr_u8(&mut l);
-                self.reg_hl.lo = l;
-                8
-            },
-            0x1E => {
-                let mut byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.rr_u8(&mut byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), byte);
-                16
-            },
-            0x1F => { 
-                let mut a = self.reg_af.hi;
-                self.rr_u8(&mut a);
-                self.reg_af.hi = a;
-                8
-            },
-            0x20 => {
-                let b = self.reg_bc.hi; 
-                self.reg_bc.hi = self.sla_u8(b);
-                8
-            },
-            0x21 => {
-                let c = self.reg_bc.lo; 
-                self.reg_bc.lo = self.sla_u8(c);
-                8
-            },
-            0x22 => {
-                let d = self.reg_de.hi; 
-                self.reg_de.hi = self.sla_u8(d);
-                8
-            },
-            0x23 => {
-                let e = self.reg_de.lo; 
-                self.reg_de.lo = self.sla_u8(e);
-                8
-            },
-            0x24 => {
-                let h = self.reg_hl.hi; 
-                self.reg_hl.hi = self.sla_u8(h);
-                8
-            },
-            0x25 => {
-                let l = self.reg_hl.lo; 
-                self.reg_hl.lo = self.sla_u8(l);
-                8
-            },
-            0x26 => {
-                let byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                let res = self.sla_u8(byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), res);
-                16
-            },
-            0x27 => {
-                let a = self.reg_af.hi; 
-                self.reg_af.hi = self.sla_u8(a);
-                8
-            },
-            0x28 => {
-                let b = self.reg_bc.hi; 
-                self.reg_bc.hi = self.sra_u8(b);
-                8
-            },
-            0x29 => {
-                let c = self.reg_bc.lo; 
-                self.reg_bc.lo = self.sra_u8(c);
-                8
-            },
-            0x2A => {
-                let d = self.reg_de.hi; 
-                self.reg_de.hi = self.sra_u8(d);
-                8
-            },
-            0x2B => {
-                let e = self.reg_de.lo; 
-                self.reg_de.lo = self.sra_u8(e);
-                8
-            },
-            0x2C => {
-                let h = self.reg_hl.hi; 
-                self.reg_hl.hi = self.sra_u8(h);
-                8
-            },
-            0x2D => {
-                let l = self.reg_hl.lo; 
-                self.reg_hl.lo = self.sra_u8(l);
-                8
-            },
-            0x2E => {
-                let byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                let res = self.sra_u8(byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), res);
-                16
-            },
-            0x2F => {
-                let a = self.reg_af.hi; 
-                self.reg_af.hi = self.sra_u8(a);
-                8
-            },
-            0x30 => {
-                swap_nybbles(&mut self.reg_bc.hi);
-                let is_zero = self.reg_bc.hi == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                8
-            },
-            0x31 => {
-                swap_nybbles(&mut self.reg_bc.lo);
-                let is_zero = self.reg_bc.lo == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                8
-            },
-            0x32 => {
-                swap_nybbles(&mut self.reg_de.hi);
-                let is_zero = self.reg_de.hi == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                8
-            },
-            0x33 => {
-                swap_nybbles(&mut self.reg_de.lo);
-                let is_zero = self.reg_de.lo == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                8
-            },
-            0x34 => {
-                swap_nybbles(&mut self.reg_hl.hi);
-                let is_zero = self.reg_hl.hi == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                8
-            },
-            0x35 => {
-                swap_nybbles(&mut self.reg_hl.lo);
-                let is_zero = self.reg_hl.lo == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                8
-            },
-            0x36 => {
-                swap_nybbles(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize]);
-                let is_zero = self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize] == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                16
-            },
-            0x37 => {
-                swap_nybbles(&mut self.reg_af.hi);
-                let is_zero = self.reg_af.hi == 0;
-                self.update_zero_flag(is_zero);
-                self.update_subtract_flag(false);
-                self.update_carry_flag(false);
-                self.update_half_carry_flag(false);
-                8
-            },
-            0x38 => {
-                let b = self.reg_bc.hi; 
-                self.reg_bc.hi = self.srl_u8(b);
-                8
-            },
-            0x39 => {
-                let c = self.reg_bc.lo; 
-                self.reg_bc.lo = self.srl_u8(c);
-                8
-            },
-            0x3A => {
-                let d = self.reg_de.hi; 
-                self.reg_de.hi = self.srl_u8(d);
-                8
-            },
-            0x3B => {
-                let e = self.reg_de.lo; 
-                self.reg_de.lo = self.srl_u8(e);
-                8
-            },
-            0x3C => {
-                let h = self.reg_hl.hi; 
-                self.reg_hl.hi = self.srl_u8(h);
-                8
-            },
-            0x3D => {
-                let l = self.reg_hl.lo; 
-                self.reg_hl.lo = self.srl_u8(l);
-                8
-            },
-            0x3E => {
-                let byte = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                let res = self.srl_u8(byte);
-                self.memory_manager.borrow_mut().write_memory(self.reg_hl.get_pair(), res);
-                16
-            },
-            0x3F => {
-                let a = self.reg_af.hi; 
-                self.reg_af.hi = self.srl_u8(a);
-                8
-            },
-            0x40 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x41 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x42 => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x43 => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x44 => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x45 => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x46 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
-            },
-            0x47 => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 0));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x48 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x49 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x4A => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x4B => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x4C => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x4D => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x4E => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
-            },
-            0x4F => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 1));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x50 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x51 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x52 => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x53 => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x54 => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x55 => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x56 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
-            },
-            0x57 => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 2));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x58 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x59 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x5A => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x5B => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x5C => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x5D => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x5E => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
-            },
-            0x5F => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 3));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x60 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x61 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x62 => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x63 => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x64 => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x65 => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
-            },
-            0x66 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
+pub struct Cpu {
+
+    // Register pairs
+    reg_af: RegisterPair,
+    reg_bc: RegisterPair,
+    reg_de: RegisterPair,
+    reg_hl: RegisterPair,
+    reg_sp: RegisterPair,
+
+    // Program counter
+    reg_pc: u16,
+
+    // Master interrupt switch
+    interrupts_enabled: bool,
+
+    // Instructions left to run before EI takes effect
+    enable_interrupts_delay: u8,
+
+    halted: bool,
+
+    // Set when HALT is skipped with an interrupt
+    // pending, so the next byte is read twice
+    halt_bug: bool,
+
+    // Low power mode entered by STOP
+    stopped: bool
+}
+
+impl Cpu {
+
+    /// Default constructor.
+    pub fn new() -> Cpu {
+        Cpu {
+            reg_af: RegisterPair::new(0x01B0),
+            reg_bc: RegisterPair::new(0x0013),
+            reg_de: RegisterPair::new(0x00D8),
+            reg_hl: RegisterPair::new(0x014D),
+            reg_sp: RegisterPair::new(0xFFFE),
+            reg_pc: 0x0100,
+            interrupts_enabled: false,
+            enable_interrupts_delay: 0,
+            halted: false,
+            halt_bug: false,
+            stopped: false
+        }
+    }
+
+    /// Creates a CPU with every register zeroed,
+    /// as it is at power on before the boot ROM runs.
+    pub fn new_for_boot_rom() -> Cpu {
+        Cpu {
+            reg_af: RegisterPair::new(0),
+            reg_bc: RegisterPair::new(0),
+            reg_de: RegisterPair::new(0),
+            reg_hl: RegisterPair::new(0),
+            reg_sp: RegisterPair::new(0),
+            reg_pc: 0,
+            interrupts_enabled: false,
+            enable_interrupts_delay: 0,
+            halted: false,
+            halt_bug: false,
+            stopped: false
+        }
+    }
+
+    /// Getter for the program counter.
+    pub fn get_reg_pc(&mut self) -> u16 {
+        self.reg_pc
+    }
+
+    /// Setter for the program counter.
+    pub fn set_reg_pc(&mut self, reg_pc: u16) {
+        self.reg_pc = reg_pc;
+    } 
+
+    /// Setter for the halted switch.
+    pub fn set_halted(&mut self, halted: bool) {
+        self.halted = halted;
+    }
+
+    /// Undoes a pending HALT bug when an interrupt is about
+    /// to be dispatched. That only happens for EI followed by
+    /// HALT, where the interrupt returns to the HALT itself
+    /// so it runs again once the handler is done.
+    pub fn cancel_halt_bug(&mut self) {
+        if self.halt_bug {
+            self.halt_bug = false;
+            self.reg_pc = self.reg_pc.wrapping_sub(1);
+        }
+    }
+
+    /// Getter for whether the CPU has been stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Setter for the stopped switch.
+    pub fn set_stopped(&mut self, stopped: bool) {
+        self.stopped = stopped;
+    }
+
+    /// Getter for the interrupt switch.
+    pub fn get_interrupts_enabled(&mut self) -> bool {
+        self.interrupts_enabled
+    }
+
+    /// Setter for the interrupt switch.
+    pub fn set_interrupts_enabled(&mut self, interrupts_enabled: bool) {
+        self.interrupts_enabled = interrupts_enabled;
+    }
+
+    /// Pushes a word onto the stack.
+    pub fn stack_push(&mut self, val: u16, memory_manager: &mut MemoryManager) {
+        self.stack_push_byte((val >> 8) as u8, memory_manager);
+        self.stack_push_byte((val & 0xFF) as u8, memory_manager);
+    }
+
+    /// Pushes a single byte onto the stack.
+    pub fn stack_push_byte(&mut self, byte: u8, memory_manager: &mut MemoryManager) {
+        let sp = self.reg_sp.get_pair().wrapping_sub(1);
+        self.reg_sp.set_pair(sp);
+        memory_manager.write_memory(sp, byte);
+    }
+
+    /// Pops a word off the stack.
+    pub fn stack_pop(&mut self, memory_manager: &mut MemoryManager) -> u16 {
+        let prev = self.reg_sp.get_pair();
+        let mut word = (memory_manager.read_memory(prev.wrapping_add(1)) as u16) << 8;
+        word |= memory_manager.read_memory(prev) as u16;
+        self.reg_sp.set_pair(prev.wrapping_add(2));
+        word
+    } 
+
+    /// Calls a subroutine at a given address.
+    pub fn call_routine(&mut self, address: u16, memory_manager: &mut MemoryManager) {
+        let pc = self.reg_pc;
+        self.stack_push(pc, memory_manager);
+        self.reg_pc = address;
+    }
+
+    /// Updates the bit for the zero flag.
+    pub fn update_zero_flag(&mut self, result: bool) {
+        if result {
+            set_bit(&mut self.reg_af.lo, 7);
+        }
+        else {
+            reset_bit(&mut self.reg_af.lo, 7);
+        }
+    }
+
+    /// Updates the bit for the subtract flag.
+    pub fn update_subtract_flag(&mut self, sub_occurred: bool) {
+        if sub_occurred {
+            set_bit(&mut self.reg_af.lo, 6);
+        }
+        else {
+            reset_bit(&mut self.reg_af.lo, 6);
+        }
+    }
+
+    /// Updates the bit for the half carry flag.
+    pub fn update_half_carry_flag(&mut self, half_carry_occurred: bool) {
+        if half_carry_occurred {
+            set_bit(&mut self.reg_af.lo, 5);
+        }
+        else {
+            reset_bit(&mut self.reg_af.lo, 5);
+        }
+    }
+
+    /// Updates the bit for the carry flag.
+    pub fn update_carry_flag(&mut self, carry_occurred: bool) {
+        if carry_occurred {
+            set_bit(&mut self.reg_af.lo, 4);
+        }
+        else {
+            reset_bit(&mut self.reg_af.lo, 4);
+        }
+    }
+
+    /// Moves the PC and executes the next opcode,
+    /// then returns the number of cycles it 
+    /// took.
+    pub fn interpret_opcode(&mut self, memory_manager: &mut MemoryManager) -> Result<i32, EmulatorError> {
+
+        // Don't run if halted
+        if self.halted {
+            return Ok(4);
+        }
+
+        let start_pc = self.reg_pc;
+        let instruction = {
+            let reg_pc = &mut self.reg_pc;
+            let halt_bug = &mut self.halt_bug;
+            decode(|| {
+                let byte = memory_manager.read_memory(*reg_pc);
+
+                // The halt bug reads the first
+                // byte without moving past it
+                if *halt_bug {
+                    *halt_bug = false;
+                }
+                else {
+                    *reg_pc = reg_pc.wrapping_add(1);
+                }
+                byte
+            })
+        };
+
+        if let Instruction::Undefined(opcode) = instruction {
+            return Err(EmulatorError::UndefinedOpcode { pc: start_pc, opcode });
+        }
+        let cycles = self.execute(instruction, memory_manager);
+
+        // EI enables interrupts only once the
+        // instruction after it has finished
+        if self.enable_interrupts_delay > 0 {
+            self.enable_interrupts_delay -= 1;
+            if self.enable_interrupts_delay == 0 {
+                self.interrupts_enabled = true;
+            }
+        }
+        Ok(cycles)
+    }
+
+    /// Executes a decoded instruction whose bytes have
+    /// already been read, returning the cycles it took.
+    pub fn execute(&mut self, instruction: Instruction, memory_manager: &mut MemoryManager) -> i32 {
+        let mut branch_taken = false;
+        match instruction {
+            Instruction::Nop => {},
+            Instruction::Stop => {
+
+                // STOP resets DIV as it stops the clock
+                memory_manager.reset_div();
+                self.stopped = true;
             },
-            0x67 => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 4));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Halt => {
+
+                // HALT ends straight away if an interrupt is already
+                // pending. Without IME set, the CPU then fails to
+                // increment PC after reading the next opcode.
+                let pending = memory_manager.read_memory(0xFF0F) & memory_manager.read_memory(0xFFFF) & 0x1F;
+                if pending == 0 {
+                    self.halted = true;
+                }
+                else if !self.interrupts_enabled {
+                    self.halt_bug = true;
+                }
             },
-            0x68 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Di => {
+                self.interrupts_enabled = false;
+                self.enable_interrupts_delay = 0;
             },
-            0x69 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Ei => {
+
+                // A second EI in a row doesn't push
+                // back the one already pending
+                if self.enable_interrupts_delay == 0 {
+                    self.enable_interrupts_delay = 2;
+                }
             },
-            0x6A => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Ld8 { dest, src } => {
+                let byte = self.read_reg8(src, memory_manager);
+                self.write_reg8(dest, byte, memory_manager);
             },
-            0x6B => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Ld8Immediate { dest, value } => self.write_reg8(dest, value, memory_manager),
+            Instruction::LdIndirectA(indirect) => {
+                let address = self.indirect_address(indirect);
+                memory_manager.write_memory(address, self.reg_af.hi);
             },
-            0x6C => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::LdAIndirect(indirect) => {
+                let address = self.indirect_address(indirect);
+                self.reg_af.hi = memory_manager.read_memory(address);
             },
-            0x6D => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::LdAddressA(address) => memory_manager.write_memory(address, self.reg_af.hi),
+            Instruction::LdAAddress(address) => self.reg_af.hi = memory_manager.read_memory(address),
+            Instruction::LdhAddressA(offset) => memory_manager.write_memory(0xFF00 | offset as u16, self.reg_af.hi),
+            Instruction::LdhAAddress(offset) => self.reg_af.hi = memory_manager.read_memory(0xFF00 | offset as u16),
+            Instruction::LdhCA => memory_manager.write_memory(0xFF00 | self.reg_bc.lo as u16, self.reg_af.hi),
+            Instruction::LdhAC => self.reg_af.hi = memory_manager.read_memory(0xFF00 | self.reg_bc.lo as u16),
+            Instruction::Ld16Immediate { dest, value } => self.write_reg16(dest, value),
+            Instruction::LdAddressSp(address) => {
+                memory_manager.write_memory(address, self.reg_sp.lo);
+                memory_manager.write_memory(address.wrapping_add(1), self.reg_sp.hi);
             },
-            0x6E => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
+            Instruction::LdSpHl => self.reg_sp.set_pair(self.reg_hl.get_pair()),
+            Instruction::LdHlSpOffset(offset) => {
+                let sum = self.add_sp_offset(offset);
+                self.reg_hl.set_pair(sum);
             },
-            0x6F => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 5));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Push(reg) => {
+                let val = self.read_reg16_stack(reg);
+                self.stack_push(val, memory_manager);
             },
-            0x70 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Pop(reg) => {
+                let val = self.stack_pop(memory_manager);
+                self.write_reg16_stack(reg, val);
             },
-            0x71 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Inc8(target) => {
+                let mut byte = self.read_reg8(target, memory_manager);
+                self.inc_u8(&mut byte);
+                self.write_reg8(target, byte, memory_manager);
             },
-            0x72 => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Dec8(target) => {
+                let mut byte = self.read_reg8(target, memory_manager);
+                self.dec_u8(&mut byte);
+                self.write_reg8(target, byte, memory_manager);
             },
-            0x73 => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Inc16(target) => {
+                let val = self.read_reg16(target);
+                self.write_reg16(target, val.wrapping_add(1));
             },
-            0x74 => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Dec16(target) => {
+                let val = self.read_reg16(target);
+                self.write_reg16(target, val.wrapping_sub(1));
             },
-            0x75 => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::AddHl(src) => {
+                let val = self.read_reg16(src);
+                self.add_u16_hl(val);
             },
-            0x76 => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
+            Instruction::AddSpOffset(offset) => {
+                let sum = self.add_sp_offset(offset);
+                self.reg_sp.set_pair(sum);
             },
-            0x77 => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 6));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Alu { op, src } => {
+                let byte = self.read_reg8(src, memory_manager);
+                self.alu(op, byte);
             },
-            0x78 => {
-                let reg = self.reg_bc.hi;
-                self.update_zero_flag(!test_bit(reg, 7));
-                self.update_subtract_flag(false);
+            Instruction::AluImmediate { op, value } => self.alu(op, value),
+            Instruction::Daa => self.daa(),
+            Instruction::Cpl => {
+                self.reg_af.hi = !self.reg_af.hi;
+                self.update_subtract_flag(true);
                 self.update_half_carry_flag(true);
-                8
             },
-            0x79 => {
-                let reg = self.reg_bc.lo;
-                self.update_zero_flag(!test_bit(reg, 7));
+            Instruction::Scf => {
                 self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+                self.update_half_carry_flag(false);
+                self.update_carry_flag(true);
             },
-            0x7A => {
-                let reg = self.reg_de.hi;
-                self.update_zero_flag(!test_bit(reg, 7));
+            Instruction::Ccf => {
+                let carry = test_bit(self.reg_af.lo, 4);
                 self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+                self.update_half_carry_flag(false);
+                self.update_carry_flag(!carry);
             },
-            0x7B => {
-                let reg = self.reg_de.lo;
-                self.update_zero_flag(!test_bit(reg, 7));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Rlca | Instruction::Rrca | Instruction::Rla | Instruction::Rra => {
+                let mut a = self.reg_af.hi;
+                match instruction {
+                    Instruction::Rlca => self.rlc_u8(&mut a),
+                    Instruction::Rrca => self.rrc_u8(&mut a),
+                    Instruction::Rla => self.rl_u8(&mut a),
+                    _ => self.rr_u8(&mut a)
+                }
+                self.reg_af.hi = a;
+                self.update_zero_flag(false);
             },
-            0x7C => {
-                let reg = self.reg_hl.hi;
-                self.update_zero_flag(!test_bit(reg, 7));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Jp { condition, address } => {
+                branch_taken = self.condition_met(condition);
+                if branch_taken {
+                    self.reg_pc = address;
+                }
             },
-            0x7D => {
-                let reg = self.reg_hl.lo;
-                self.update_zero_flag(!test_bit(reg, 7));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::JpHl => self.reg_pc = self.reg_hl.get_pair(),
+            Instruction::Jr { condition, offset } => {
+                branch_taken = self.condition_met(condition);
+                if branch_taken {
+                    self.reg_pc = self.reg_pc.wrapping_add(offset as i16 as u16);
+                }
             },
-            0x7E => {
-                let val = self.memory_manager.borrow_mut().read_memory(self.reg_hl.get_pair());
-                self.update_zero_flag(!test_bit(val, 7));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                16
+            Instruction::Call { condition, address } => {
+                branch_taken = self.condition_met(condition);
+                if branch_taken {
+                    self.call_routine(address, memory_manager);
+                }
             },
-            0x7F => {
-                let reg = self.reg_af.hi;
-                self.update_zero_flag(!test_bit(reg, 7));
-                self.update_subtract_flag(false);
-                self.update_half_carry_flag(true);
-                8
+            Instruction::Ret { condition } => {
+                branch_taken = self.condition_met(condition);
+                if branch_taken {
+                    self.reg_pc = self.stack_pop(memory_manager);
+                }
             },
-            0x80 => { reset_bit(&mut self.reg_bc.hi, 0); 8 },
-            0x81 => { reset_bit(&mut self.reg_bc.lo, 0); 8 },
-            0x82 => { reset_bit(&mut self.reg_de.hi, 0); 8 },
-            0x83 => { reset_bit(&mut self.reg_de.lo, 0); 8 },
-            0x84 => { reset_bit(&mut self.reg_hl.hi, 0); 8 },
-            0x85 => { reset_bit(&mut self.reg_hl.lo, 0); 8 },
-            0x86 => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 0); 16 },
-            0x87 => { reset_bit(&mut self.reg_af.hi, 0); 8 },
-            0x88 => { reset_bit(&mut self.reg_bc.hi, 1); 8 },
-            0x89 => { reset_bit(&mut self.reg_bc.lo, 1); 8 },
-            0x8A => { reset_bit(&mut self.reg_de.hi, 1); 8 },
-            0x8B => { reset_bit(&mut self.reg_de.lo, 1); 8 },
-            0x8C => { reset_bit(&mut self.reg_hl.hi, 1); 8 },
-            0x8D => { reset_bit(&mut self.reg_hl.lo, 1); 8 },
-            0x8E => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 1); 16 },
-            0x8F => { reset_bit(&mut self.reg_af.hi, 1); 8 },
-            0x90 => { reset_bit(&mut self.reg_bc.hi, 2); 8 },
-            0x91 => { reset_bit(&mut self.reg_bc.lo, 2); 8 },
-            0x92 => { reset_bit(&mut self.reg_de.hi, 2); 8 },
-            0x93 => { reset_bit(&mut self.reg_de.lo, 2); 8 },
-            0x94 => { reset_bit(&mut self.reg_hl.hi, 2); 8 },
-            0x95 => { reset_bit(&mut self.reg_hl.lo, 2); 8 },
-            0x96 => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 2); 16 },
-            0x97 => { reset_bit(&mut self.reg_af.hi, 2); 8 },
-            0x98 => { reset_bit(&mut self.reg_bc.hi, 3); 8 },
-            0x99 => { reset_bit(&mut self.reg_bc.lo, 3); 8 },
-            0x9A => { reset_bit(&mut self.reg_de.hi, 3); 8 },
-            0x9B => { reset_bit(&mut self.reg_de.lo, 3); 8 },
-            0x9C => { reset_bit(&mut self.reg_hl.hi, 3); 8 },
-            0x9D => { reset_bit(&mut self.reg_hl.lo, 3); 8 },
-            0x9E => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 3); 16 },
-            0x9F => { reset_bit(&mut self.reg_af.hi, 3); 8 },
-            0xA0 => { reset_bit(&mut self.reg_bc.hi, 4); 8 },
-            0xA1 => { reset_bit(&mut self.reg_bc.lo, 4); 8 },
-            0xA2 => { reset_bit(&mut self.reg_de.hi, 4); 8 },
-            0xA3 => { reset_bit(&mut self.reg_de.lo, 4); 8 },
-            0xA4 => { reset_bit(&mut self.reg_hl.hi, 4); 8 },
-            0xA5 => { reset_bit(&mut self.reg_hl.lo, 4); 8 },
-            0xA6 => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 4); 16 },
-            0xA7 => { reset_bit(&mut self.reg_af.hi, 4); 8 },
-            0xA8 => { reset_bit(&mut self.reg_bc.hi, 5); 8 },
-            0xA9 => { reset_bit(&mut self.reg_bc.lo, 5); 8 },
-            0xAA => { reset_bit(&mut self.reg_de.hi, 5); 8 },
-            0xAB => { reset_bit(&mut self.reg_de.lo, 5); 8 },
-            0xAC => { reset_bit(&mut self.reg_hl.hi, 5); 8 },
-            0xAD => { reset_bit(&mut self.reg_hl.lo, 5); 8 },
-            0xAE => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 5); 16 },
-            0xAF => { reset_bit(&mut self.reg_af.hi, 5); 8 },
-            0xB0 => { reset_bit(&mut self.reg_bc.hi, 6); 8 },
-            0xB1 => { reset_bit(&mut self.reg_bc.lo, 6); 8 },
-            0xB2 => { reset_bit(&mut self.reg_de.hi, 6); 8 },
-            0xB3 => { reset_bit(&mut self.reg_de.lo, 6); 8 },
-            0xB4 => { reset_bit(&mut self.reg_hl.hi, 6); 8 },
-            0xB5 => { reset_bit(&mut self.reg_hl.lo, 6); 8 },
-            0xB6 => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 6); 16 },
-            0xB7 => { reset_bit(&mut self.reg_af.hi, 6); 8 },
-            0xB8 => { reset_bit(&mut self.reg_bc.hi, 7); 8 },
-            0xB9 => { reset_bit(&mut self.reg_bc.lo, 7); 8 },
-            0xBA => { reset_bit(&mut self.reg_de.hi, 7); 8 },
-            0xBB => { reset_bit(&mut self.reg_de.lo, 7); 8 },
-            0xBC => { reset_bit(&mut self.reg_hl.hi, 7); 8 },
-            0xBD => { reset_bit(&mut self.reg_hl.lo, 7); 8 },
-            0xBE => { reset_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 7); 16 },
-            0xBF => { reset_bit(&mut self.reg_af.hi, 7); 8 },
-            0xC0 => { set_bit(&mut self.reg_bc.hi, 0); 8 },
-            0xC1 => { set_bit(&mut self.reg_bc.lo, 0); 8 },
-            0xC2 => { set_bit(&mut self.reg_de.hi, 0); 8 },
-            0xC3 => { set_bit(&mut self.reg_de.lo, 0); 8 },
-            0xC4 => { set_bit(&mut self.reg_hl.hi, 0); 8 },
-            0xC5 => { set_bit(&mut self.reg_hl.lo, 0); 8 },
-            0xC6 => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 0); 16 },
-            0xC7 => { set_bit(&mut self.reg_af.hi, 0); 8 },
-            0xC8 => { set_bit(&mut self.reg_bc.hi, 1); 8 },
-            0xC9 => { set_bit(&mut self.reg_bc.lo, 1); 8 },
-            0xCA => { set_bit(&mut self.reg_de.hi, 1); 8 },
-            0xCB => { set_bit(&mut self.reg_de.lo, 1); 8 },
-            0xCC => { set_bit(&mut self.reg_hl.hi, 1); 8 },
-            0xCD => { set_bit(&mut self.reg_hl.lo, 1); 8 },
-            0xCE => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 1); 16 },
-            0xCF => { set_bit(&mut self.reg_af.hi, 1); 8 },
-            0xD0 => { set_bit(&mut self.reg_bc.hi, 2); 8 },
-            0xD1 => { set_bit(&mut self.reg_bc.lo, 2); 8 },
-            0xD2 => { set_bit(&mut self.reg_de.hi, 2); 8 },
-            0xD3 => { set_bit(&mut self.reg_de.lo, 2); 8 },
-            0xD4 => { set_bit(&mut self.reg_hl.hi, 2); 8 },
-            0xD5 => { set_bit(&mut self.reg_hl.lo, 2); 8 },
-            0xD6 => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 2); 16 },
-            0xD7 => { set_bit(&mut self.reg_af.hi, 2); 8 },
-            0xD8 => { set_bit(&mut self.reg_bc.hi, 3); 8 },
-            0xD9 => { set_bit(&mut self.reg_bc.lo, 3); 8 },
-            0xDA => { set_bit(&mut self.reg_de.hi, 3); 8 },
-            0xDB => { set_bit(&mut self.reg_de.lo, 3); 8 },
-            0xDC => { set_bit(&mut self.reg_hl.hi, 3); 8 },
-            0xDD => { set_bit(&mut self.reg_hl.lo, 3); 8 },
-            0xDE => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 3); 16 },
-            0xDF => { set_bit(&mut self.reg_af.hi, 3); 8 },
-            0xE0 => { set_bit(&mut self.reg_bc.hi, 4); 8 },
-            0xE1 => { set_bit(&mut self.reg_bc.lo, 4); 8 },
-            0xE2 => { set_bit(&mut self.reg_de.hi, 4); 8 },
-            0xE3 => { set_bit(&mut self.reg_de.lo, 4); 8 },
-            0xE4 => { set_bit(&mut self.reg_hl.hi, 4); 8 },
-            0xE5 => { set_bit(&mut self.reg_hl.lo, 4); 8 },
-            0xE6 => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 4); 16 },
-            0xE7 => { set_bit(&mut self.reg_af.hi, 4); 8 },
-            0xE8 => { set_bit(&mut self.reg_bc.hi, 5); 8 },
-            0xE9 => { set_bit(&mut self.reg_bc.lo, 5); 8 },
-            0xEA => { set_bit(&mut self.reg_de.hi, 5); 8 },
-            0xEB => { set_bit(&mut self.reg_de.lo, 5); 8 },
-            0xEC => { set_bit(&mut self.reg_hl.hi, 5); 8 },
-            0xED => { set_bit(&mut self.reg_hl.lo, 5); 8 },
-            0xEE => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 5); 16 },
-            0xEF => { set_bit(&mut self.reg_af.hi, 5); 8 },
-            0xF0 => { set_bit(&mut self.reg_bc.hi, 6); 8 },
-            0xF1 => { set_bit(&mut self.reg_bc.lo, 6); 8 },
-            0xF2 => { set_bit(&mut self.reg_de.hi, 6); 8 },
-            0xF3 => { set_bit(&mut self.reg_de.lo, 6); 8 },
-            0xF4 => { set_bit(&mut self.reg_hl.hi, 6); 8 },
-            0xF5 => { set_bit(&mut self.reg_hl.lo, 6); 8 },
-            0xF6 => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 6); 16 },
-            0xF7 => { set_bit(&mut self.reg_af.hi, 6); 8 },
-            0xF8 => { set_bit(&mut self.reg_bc.hi, 7); 8 },
-            0xF9 => { set_bit(&mut self.reg_bc.lo, 7); 8 },
-            0xFA => { set_bit(&mut self.reg_de.hi, 7); 8 },
-            0xFB => { set_bit(&mut self.reg_de.lo, 7); 8 },
-            0xFC => { set_bit(&mut self.reg_hl.hi, 7); 8 },
-            0xFD => { set_bit(&mut self.reg_hl.lo, 7); 8 },
-            0xFE => { set_bit(&mut self.memory_manager.borrow_mut().memory[self.reg_hl.get_pair() as usize], 7); 16 },
-            0xFF => { set_bit(&mut self.reg_af.hi, 7); 8 },
-            _ => panic!("Undefined extended opcode: 0x{:02X}", opcode)
+            Instruction::Reti => {
+
+                // Unlike EI, RETI enables interrupts immediately
+                self.interrupts_enabled = true;
+                self.enable_interrupts_delay = 0;
+                self.reg_pc = self.stack_pop(memory_manager);
+            },
+            Instruction::Rst(address) => self.call_routine(address as u16, memory_manager),
+            Instruction::Shift { op, target } => {
+                let mut byte = self.read_reg8(target, memory_manager);
+                match op {
+                    ShiftOp::Rlc => self.rlc_u8(&mut byte),
+                    ShiftOp::Rrc => self.rrc_u8(&mut byte),
+                    ShiftOp::Rl => self.rl_u8(&mut byte),
+                    ShiftOp::Rr => self.rr_u8(&mut byte),
+                    ShiftOp::Sla => byte = self.sla_u8(byte),
+                    ShiftOp::Sra => byte = self.sra_u8(byte),
+                    ShiftOp::Swap => {
+                        swap_nybbles(&mut byte);
+                        self.update_zero_flag(byte == 0);
+                        self.update_subtract_flag(false);
+                        self.update_half_carry_flag(false);
+                        self.update_carry_flag(false);
+                    },
+                    ShiftOp::Srl => byte = self.srl_u8(byte)
+                }
+                self.write_reg8(target, byte, memory_manager);
+            },
+            Instruction::Bit { bit, target } => {
+                let byte = self.read_reg8(target, memory_manager);
+                self.update_zero_flag(!test_bit(byte, bit));
+                self.update_subtract_flag(false);
+                self.update_half_carry_flag(true);
+            },
+            Instruction::Res { bit, target } => {
+                let mut byte = self.read_reg8(target, memory_manager);
+                reset_bit(&mut byte, bit);
+                self.write_reg8(target, byte, memory_manager);
+            },
+            Instruction::Set { bit, target } => {
+                let mut byte = self.read_reg8(target, memory_manager);
+                set_bit(&mut byte, bit);
+                self.write_reg8(target, byte, memory_manager);
+            },
+            Instruction::Undefined(_) => {}
+        }
+        instruction.get_cycles(branch_taken)
+    }
+
+    /// Reads an 8-bit operand.
+    fn read_reg8(&mut self, reg: Reg8, memory_manager: &mut MemoryManager) -> u8 {
+        match reg {
+            Reg8::B => self.reg_bc.hi,
+            Reg8::C => self.reg_bc.lo,
+            Reg8::D => self.reg_de.hi,
+            Reg8::E => self.reg_de.lo,
+            Reg8::H => self.reg_hl.hi,
+            Reg8::L => self.reg_hl.lo,
+            Reg8::IndirectHL => memory_manager.read_memory(self.reg_hl.get_pair()),
+            Reg8::A => self.reg_af.hi
+        }
+    }
+
+    /// Writes an 8-bit operand.
+    fn write_reg8(&mut self, reg: Reg8, byte: u8, memory_manager: &mut MemoryManager) {
+        match reg {
+            Reg8::B => self.reg_bc.hi = byte,
+            Reg8::C => self.reg_bc.lo = byte,
+            Reg8::D => self.reg_de.hi = byte,
+            Reg8::E => self.reg_de.lo = byte,
+            Reg8::H => self.reg_hl.hi = byte,
+            Reg8::L => self.reg_hl.lo = byte,
+            Reg8::IndirectHL => memory_manager.write_memory(self.reg_hl.get_pair(), byte),
+            Reg8::A => self.reg_af.hi = byte
+        }
+    }
+
+    /// Reads a register pair.
+    fn read_reg16(&mut self, reg: Reg16) -> u16 {
+        match reg {
+            Reg16::BC => self.reg_bc.get_pair(),
+            Reg16::DE => self.reg_de.get_pair(),
+            Reg16::HL => self.reg_hl.get_pair(),
+            Reg16::SP => self.reg_sp.get_pair()
+        }
+    }
+
+    /// Writes a register pair.
+    fn write_reg16(&mut self, reg: Reg16, val: u16) {
+        match reg {
+            Reg16::BC => self.reg_bc.set_pair(val),
+            Reg16::DE => self.reg_de.set_pair(val),
+            Reg16::HL => self.reg_hl.set_pair(val),
+            Reg16::SP => self.reg_sp.set_pair(val)
+        }
+    }
+
+    /// Reads a register pair for PUSH.
+    fn read_reg16_stack(&mut self, reg: Reg16Stack) -> u16 {
+        match reg {
+            Reg16Stack::BC => self.reg_bc.get_pair(),
+            Reg16Stack::DE => self.reg_de.get_pair(),
+            Reg16Stack::HL => self.reg_hl.get_pair(),
+            Reg16Stack::AF => self.reg_af.get_pair()
+        }
+    }
+
+    /// Writes a register pair for POP. The low
+    /// nibble of F always reads as 0.
+    fn write_reg16_stack(&mut self, reg: Reg16Stack, val: u16) {
+        match reg {
+            Reg16Stack::BC => self.reg_bc.set_pair(val),
+            Reg16Stack::DE => self.reg_de.set_pair(val),
+            Reg16Stack::HL => self.reg_hl.set_pair(val),
+            Reg16Stack::AF => self.reg_af.set_pair(val & 0xFFF0)
+        }
+    }
+
+    /// Returns the address of an indirect operand,
+    /// moving HL on afterwards if the operand says to.
+    fn indirect_address(&mut self, indirect: Indirect) -> u16 {
+        match indirect {
+            Indirect::BC => self.reg_bc.get_pair(),
+            Indirect::DE => self.reg_de.get_pair(),
+            Indirect::HLIncrement => {
+                let hl = self.reg_hl.get_pair();
+                self.reg_hl.set_pair(hl.wrapping_add(1));
+                hl
+            },
+            Indirect::HLDecrement => {
+                let hl = self.reg_hl.get_pair();
+                self.reg_hl.set_pair(hl.wrapping_sub(1));
+                hl
+            }
+        }
+    }
+
+    /// Returns whether a jump's condition holds.
+    /// Jumps without one are always taken.
+    fn condition_met(&self, condition: Option<Condition>) -> bool {
+        match condition {
+            None => true,
+            Some(Condition::NotZero) => !test_bit(self.reg_af.lo, 7),
+            Some(Condition::Zero) => test_bit(self.reg_af.lo, 7),
+            Some(Condition::NotCarry) => !test_bit(self.reg_af.lo, 4),
+            Some(Condition::Carry) => test_bit(self.reg_af.lo, 4)
         }
     }
 
+    /// Runs an arithmetic or logic operation on A.
+    fn alu(&mut self, op: AluOp, src: u8) {
+        match op {
+            AluOp::Add => self.add_u8_a(src),
+            AluOp::Adc => self.adc_reg_a(src),
+            AluOp::Sub => self.sub_u8_a(src),
+            AluOp::Sbc => self.sbc_reg_a(src),
+            AluOp::And => self.and_reg_a(src),
+            AluOp::Xor => self.xor_reg_a(src),
+            AluOp::Or => self.or_reg_a(src),
+            AluOp::Cp => self.cp_reg_a(src)
+        }
+    }
+
+    /// Adds a signed offset to SP and returns the sum,
+    /// as ADD SP,e and LD HL,SP+e do. The carry flags
+    /// come from adding the offset to the low byte.
+    fn add_sp_offset(&mut self, offset: i8) -> u16 {
+        let byte = offset as i16 as u16;
+        let sp = self.reg_sp.get_pair();
+        self.update_half_carry_flag((byte & 0x000F) + (sp & 0x000F) > 0x000F);
+        self.update_carry_flag((byte & 0x00FF) + (sp & 0x00FF) > 0x00FF);
+        self.update_zero_flag(false);
+        self.update_subtract_flag(false);
+        sp.wrapping_add(byte)
+    }
 
     /// Adds src and register A together
     /// and stores the sum in A.
@@ -2113,7 +590,7 @@ impl Cpu {
     pub fn add_u16_hl(&mut self, src: u16) {
         let hl = self.reg_hl.get_pair();
         self.reg_hl.set_pair(hl.wrapping_add(src));
-        self.update_half_carry_flag(((src & 0x7FF) + (hl & 0x7FF)) > 0x7FF);
+        self.update_half_carry_flag(((src & 0xFFF) + (hl & 0xFFF)) > 0xFFF);
         self.update_carry_flag(hl > 0xFFFF - src);
         self.update_subtract_flag(false);
     }
@@ -2154,7 +631,7 @@ impl Cpu {
         let carry = if test_bit(self.reg_af.lo, 4) { 1 } else { 0 };
         let sum = a.wrapping_sub(src).wrapping_sub(carry);
         self.reg_af.hi = sum;
-        self.update_half_carry_flag((a & 0x0F) < (sum & 0x0F) + carry);
+        self.update_half_carry_flag((a & 0x0F) < (src & 0x0F) + carry);
         self.update_carry_flag((a as u16) < (src as u16) + (carry as u16));
         self.update_zero_flag(sum == 0);
         self.update_subtract_flag(true);
@@ -2204,7 +681,7 @@ impl Cpu {
     /// Rotates a u8's bits left.
     pub fn rl_u8(&mut self, src: &mut u8) {
         let carry_occurred = *src >> 7 == 1;
-        *src = *src << 1;
+        *src <<= 1;
         if test_bit(self.reg_af.lo, 4) {
             *src |= 1;
         }
@@ -2227,7 +704,7 @@ impl Cpu {
     /// Rotates a u8's bits right.
     pub fn rr_u8(&mut self, src: &mut u8) {
         let carry_occurred = *src & 1 == 1;
-        *src = *src >> 1;
+        *src >>= 1;
         if test_bit(self.reg_af.lo, 4) {
             *src |= 1 << 7;
         }
@@ -2306,4 +783,10 @@ impl Cpu {
         self.update_half_carry_flag(false);
         self.update_carry_flag(shift >= 0x60);
     }
+}
+
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu::new()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,284 @@
+use instructions::*;
+use memory_manager::*;
+
+use std::fmt;
+
+/// Size of a switchable ROM bank.
+static ROM_BANK_SIZE: usize = 0x4000;
+
+/// An instruction decoded at an address, along
+/// with its bytes, text and timing.
+pub struct DisassembledInstruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub instruction: Instruction,
+
+    // RGBDS syntax, such as "ld a, [hl+]"
+    pub text: String,
+
+    // Cycles taken, or when the instruction is
+    // conditional, cycles taken without branching
+    pub cycles: i32,
+
+    // Cycles taken when a conditional
+    // instruction does branch
+    pub branch_cycles: Option<i32>
+}
+
+impl DisassembledInstruction {
+
+    /// Getter for the number of bytes
+    /// the instruction takes up.
+    pub fn get_length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    /// Getter for the address of the
+    /// instruction that follows this one.
+    pub fn get_next_address(&self) -> u16 {
+        self.address.wrapping_add(self.get_length())
+    }
+}
+
+impl fmt::Display for DisassembledInstruction {
+
+    /// Formats the instruction as a listing line with
+    /// its address, bytes, text and cycle count.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let cycles = match self.branch_cycles {
+            Some(branch_cycles) => format!("{}/{}", branch_cycles, self.cycles),
+            None => format!("{}", self.cycles)
+        };
+        write!(f, "{:04X}: {:<8}  {:<20} ; {}", self.address, bytes.join(" "), self.text, cycles)
+    }
+}
+
+/// Disassembles the instruction at address,
+/// reading its bytes through read_byte.
+pub fn disassemble<F: FnMut(u16) -> u8>(address: u16, mut read_byte: F) -> DisassembledInstruction {
+    let mut bytes = Vec::with_capacity(3);
+    let instruction = decode(|| {
+        let byte = read_byte(address.wrapping_add(bytes.len() as u16));
+        bytes.push(byte);
+        byte
+    });
+
+    let next_address = address.wrapping_add(bytes.len() as u16);
+    let cycles = instruction.get_cycles(false);
+    let branch_cycles = instruction.get_cycles(true);
+    DisassembledInstruction {
+        address,
+        bytes,
+        instruction,
+        text: format_instruction(&instruction, next_address),
+        cycles,
+        branch_cycles: if branch_cycles != cycles { Some(branch_cycles) } else { None }
+    }
+}
+
+/// Disassembles the instruction at address as the
+/// CPU currently sees it, through the mapped banks.
+pub fn disassemble_memory(address: u16, memory_manager: &mut MemoryManager) -> DisassembledInstruction {
+    disassemble(address, |address| memory_manager.read_memory(address))
+}
+
+/// Disassembles the instruction at address in a ROM
+/// image, with bank mapped at 0x4000-0x7FFF. Bytes past
+/// the end of the ROM or outside 0x0000-0x7FFF read
+/// as 0xFF, as on an open bus.
+pub fn disassemble_rom(rom: &[u8], bank: u16, address: u16) -> DisassembledInstruction {
+    disassemble(address, |address| {
+        let offset = match address {
+            0..=0x3FFF => address as usize,
+            0x4000..=0x7FFF => bank as usize * ROM_BANK_SIZE + (address as usize - ROM_BANK_SIZE),
+            _ => return 0xFF
+        };
+        rom.get(offset).cloned().unwrap_or(0xFF)
+    })
+}
+
+/// Disassembles every instruction from start up to end,
+/// the same way disassemble does.
+pub fn disassemble_range<F: FnMut(u16) -> u8>(start: u16, end: u16, mut read_byte: F) -> Vec<DisassembledInstruction> {
+    let mut listing = Vec::new();
+    let mut address = start as u32;
+    while address < end as u32 {
+        let line = disassemble(address as u16, &mut read_byte);
+        address += line.get_length() as u32;
+        listing.push(line);
+    }
+    listing
+}
+
+/// Formats an instruction in RGBDS syntax. Relative
+/// jumps are shown with the address they land on,
+/// found from the address after the instruction.
+pub fn format_instruction(instruction: &Instruction, next_address: u16) -> String {
+    format_instruction_with_target(instruction, next_address, None)
+}
+
+/// Formats an instruction like format_instruction, but with
+/// the target of a jump or call written as target_name,
+/// such as a label, instead of as an address.
+pub fn format_instruction_with_target(instruction: &Instruction, next_address: u16, target_name: Option<&str>) -> String {
+    let target = |address: u16| match target_name {
+        Some(name) => name.to_string(),
+        None => format!("${:04X}", address)
+    };
+    match *instruction {
+        Instruction::Nop => "nop".to_string(),
+        Instruction::Stop => "stop".to_string(),
+        Instruction::Halt => "halt".to_string(),
+        Instruction::Di => "di".to_string(),
+        Instruction::Ei => "ei".to_string(),
+        Instruction::Ld8 { dest, src } => format!("ld {}, {}", reg8_name(dest), reg8_name(src)),
+        Instruction::Ld8Immediate { dest, value } => format!("ld {}, ${:02X}", reg8_name(dest), value),
+        Instruction::LdIndirectA(indirect) => format!("ld {}, a", indirect_name(indirect)),
+        Instruction::LdAIndirect(indirect) => format!("ld a, {}", indirect_name(indirect)),
+        Instruction::LdAddressA(address) => format!("ld [${:04X}], a", address),
+        Instruction::LdAAddress(address) => format!("ld a, [${:04X}]", address),
+        Instruction::LdhAddressA(offset) => format!("ldh [${:04X}], a", 0xFF00 | offset as u16),
+        Instruction::LdhAAddress(offset) => format!("ldh a, [${:04X}]", 0xFF00 | offset as u16),
+        Instruction::LdhCA => "ldh [c], a".to_string(),
+        Instruction::LdhAC => "ldh a, [c]".to_string(),
+        Instruction::Ld16Immediate { dest, value } => format!("ld {}, ${:04X}", reg16_name(dest), value),
+        Instruction::LdAddressSp(address) => format!("ld [${:04X}], sp", address),
+        Instruction::LdSpHl => "ld sp, hl".to_string(),
+        Instruction::LdHlSpOffset(offset) => format!("ld hl, sp{}", signed_hex(offset, "+")),
+        Instruction::Push(reg) => format!("push {}", reg16_stack_name(reg)),
+        Instruction::Pop(reg) => format!("pop {}", reg16_stack_name(reg)),
+        Instruction::Inc8(target) => format!("inc {}", reg8_name(target)),
+        Instruction::Dec8(target) => format!("dec {}", reg8_name(target)),
+        Instruction::Inc16(target) => format!("inc {}", reg16_name(target)),
+        Instruction::Dec16(target) => format!("dec {}", reg16_name(target)),
+        Instruction::AddHl(src) => format!("add hl, {}", reg16_name(src)),
+        Instruction::AddSpOffset(offset) => format!("add sp, {}", signed_hex(offset, "")),
+        Instruction::Alu { op, src } => format!("{}{}", alu_prefix(op), reg8_name(src)),
+        Instruction::AluImmediate { op, value } => format!("{}${:02X}", alu_prefix(op), value),
+        Instruction::Daa => "daa".to_string(),
+        Instruction::Cpl => "cpl".to_string(),
+        Instruction::Scf => "scf".to_string(),
+        Instruction::Ccf => "ccf".to_string(),
+        Instruction::Rlca => "rlca".to_string(),
+        Instruction::Rrca => "rrca".to_string(),
+        Instruction::Rla => "rla".to_string(),
+        Instruction::Rra => "rra".to_string(),
+        Instruction::Jp { condition, address } => format!("jp {}{}", condition_prefix(condition), target(address)),
+        Instruction::JpHl => "jp hl".to_string(),
+        Instruction::Jr { condition, offset } => {
+            let address = next_address.wrapping_add(offset as i16 as u16);
+            format!("jr {}{}", condition_prefix(condition), target(address))
+        },
+        Instruction::Call { condition, address } => format!("call {}{}", condition_prefix(condition), target(address)),
+        Instruction::Ret { condition: None } => "ret".to_string(),
+        Instruction::Ret { condition: Some(condition) } => format!("ret {}", condition_name(condition)),
+        Instruction::Reti => "reti".to_string(),
+        Instruction::Rst(address) => format!("rst ${:02X}", address),
+        Instruction::Shift { op, target } => format!("{} {}", shift_name(op), reg8_name(target)),
+        Instruction::Bit { bit, target } => format!("bit {}, {}", bit, reg8_name(target)),
+        Instruction::Res { bit, target } => format!("res {}, {}", bit, reg8_name(target)),
+        Instruction::Set { bit, target } => format!("set {}, {}", bit, reg8_name(target)),
+
+        // Undefined opcodes have no mnemonic,
+        // so they're written out as data
+        Instruction::Undefined(opcode) => format!("db ${:02X}", opcode)
+    }
+}
+
+fn reg8_name(reg: Reg8) -> &'static str {
+    match reg {
+        Reg8::B => "b",
+        Reg8::C => "c",
+        Reg8::D => "d",
+        Reg8::E => "e",
+        Reg8::H => "h",
+        Reg8::L => "l",
+        Reg8::IndirectHL => "[hl]",
+        Reg8::A => "a"
+    }
+}
+
+fn reg16_name(reg: Reg16) -> &'static str {
+    match reg {
+        Reg16::BC => "bc",
+        Reg16::DE => "de",
+        Reg16::HL => "hl",
+        Reg16::SP => "sp"
+    }
+}
+
+fn reg16_stack_name(reg: Reg16Stack) -> &'static str {
+    match reg {
+        Reg16Stack::BC => "bc",
+        Reg16Stack::DE => "de",
+        Reg16Stack::HL => "hl",
+        Reg16Stack::AF => "af"
+    }
+}
+
+fn indirect_name(indirect: Indirect) -> &'static str {
+    match indirect {
+        Indirect::BC => "[bc]",
+        Indirect::DE => "[de]",
+        Indirect::HLIncrement => "[hl+]",
+        Indirect::HLDecrement => "[hl-]"
+    }
+}
+
+fn condition_name(condition: Condition) -> &'static str {
+    match condition {
+        Condition::NotZero => "nz",
+        Condition::Zero => "z",
+        Condition::NotCarry => "nc",
+        Condition::Carry => "c"
+    }
+}
+
+/// Returns the condition followed by the comma separating
+/// it from the target, or nothing if there isn't one.
+fn condition_prefix(condition: Option<Condition>) -> String {
+    match condition {
+        Some(condition) => format!("{}, ", condition_name(condition)),
+        None => String::new()
+    }
+}
+
+/// Returns the mnemonic and any leading operand of an
+/// ALU operation. ADD, ADC and SBC name A explicitly.
+fn alu_prefix(op: AluOp) -> &'static str {
+    match op {
+        AluOp::Add => "add a, ",
+        AluOp::Adc => "adc a, ",
+        AluOp::Sub => "sub ",
+        AluOp::Sbc => "sbc a, ",
+        AluOp::And => "and ",
+        AluOp::Xor => "xor ",
+        AluOp::Or => "or ",
+        AluOp::Cp => "cp "
+    }
+}
+
+fn shift_name(op: ShiftOp) -> &'static str {
+    match op {
+        ShiftOp::Rlc => "rlc",
+        ShiftOp::Rrc => "rrc",
+        ShiftOp::Rl => "rl",
+        ShiftOp::Rr => "rr",
+        ShiftOp::Sla => "sla",
+        ShiftOp::Sra => "sra",
+        ShiftOp::Swap => "swap",
+        ShiftOp::Srl => "srl"
+    }
+}
+
+/// Formats a signed offset in hex, such as -$03. Positive
+/// offsets are given the sign passed in, if any.
+fn signed_hex(offset: i8, positive_sign: &str) -> String {
+    if offset < 0 {
+        format!("-${:02X}", (offset as i16).abs())
+    }
+    else {
+        format!("{}${:02X}", positive_sign, offset)
+    }
+}
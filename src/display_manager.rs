@@ -1,14 +1,5 @@
 use memory_manager::*;
-use std::rc::Rc;
-use std::cell::RefCell;
-
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::video::Window;
-use sdl2::render::Canvas;
-use sdl2::VideoSubsystem;
-
-static SCALE: u32 = 2;
+use frame::*;
 
 pub enum DisplayColor {
     White,
@@ -17,67 +8,59 @@ pub enum DisplayColor {
     Black
 }
 
+impl DisplayColor {
+
+    /// Returns the shade index of the color.
+    pub fn get_shade(&self) -> u8 {
+        match *self {
+            DisplayColor::White => 0,
+            DisplayColor::LightGray => 1,
+            DisplayColor::DarkGray => 2,
+            DisplayColor::Black => 3
+        }
+    }
+}
+
 pub struct DisplayManager {
-    display: [[[u8; 3]; 144]; 160],
-    remaining_cycles: i32,
-    memory_manager: Rc<RefCell<MemoryManager>>,
-    canvas: Canvas<Window>
+    display: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    frame: Frame,
+    frame_count: u64,
+    remaining_cycles: i32
 }
 
 impl DisplayManager {
 
     /// Default constructor.
-    pub fn new(memory_manager: Rc<RefCell<MemoryManager>>, video_subsystem: &VideoSubsystem) -> DisplayManager {
-        
-        // Set up video
-        let window = video_subsystem.window("Rusty Boy DMG", 160 * SCALE, 144 * SCALE)
-            .opengl()
-            .build()
-            .unwrap();
-        let mut canvas = window.into_canvas().build().unwrap();
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.present();
-
+    pub fn new() -> DisplayManager {
         DisplayManager {
-            display: [[[0; 3]; 144]; 160],
-            remaining_cycles: 456,
-            memory_manager: memory_manager,
-            canvas: canvas
+            display: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame: Frame::new(),
+            frame_count: 0,
+            remaining_cycles: 456
         }
     }
 
-    /// Draws the current contents of
-    /// the display array to the canvas.
-    pub fn draw_display(&mut self) {
-        for i in 0..160 * 144 {
-            let x = i % 160;
-            let y = i / 160;
-
-            let red = self.display[x][y][0];
-            let green = self.display[x][y][1];
-            let blue = self.display[x][y][2];
-            self.canvas.set_draw_color(Color::RGB(red, green, blue));
-            let _ = self.canvas.fill_rect(Rect::new((x * SCALE as usize) as i32, (y * SCALE as usize) as i32, 1 * SCALE, 1 * SCALE));
-        }
+    /// Getter for the last completed frame.
+    pub fn get_frame(&self) -> &Frame {
+        &self.frame
+    }
 
-        self.canvas.present();
+    /// Getter for the number of frames completed.
+    pub fn get_frame_count(&self) -> u64 {
+        self.frame_count
     }
 
     /// Returns the color of a pixel given its color ID
     /// and its address.
-    pub fn get_color(&mut self, color_id: u8, address: u16) -> DisplayColor {
-        let color_palette = self.memory_manager.borrow_mut().read_memory(address);
-        let palette_hi: i32;
-        let palette_lo: i32;
-
-        match color_id {
-            0 => { palette_hi = 1; palette_lo = 0 },
-            1 => { palette_hi = 3; palette_lo = 2 },
-            2 => { palette_hi = 5; palette_lo = 4 },
-            3 => { palette_hi = 7; palette_lo = 6 },
+    pub fn get_color(&mut self, color_id: u8, address: u16, memory_manager: &mut MemoryManager) -> DisplayColor {
+        let color_palette = memory_manager.read_memory(address);
+        let (palette_hi, palette_lo) = match color_id {
+            0 => (1, 0),
+            1 => (3, 2),
+            2 => (5, 4),
+            3 => (7, 6),
             _ => { panic!("Invalid value for color ID: {}", color_id); }
-        }
+        };
 
         let mut color_bits = if (color_palette & (1 << palette_hi)) != 0 { 1 << 1 } else { 0 };
         color_bits |= if (color_palette & (1 << palette_lo)) != 0 { 1 } else { 0 };
@@ -93,24 +76,23 @@ impl DisplayManager {
 
     /// Adds the current tiles in memory to
     /// the display.
-    pub fn render_tiles(&mut self) {
+    pub fn render_tiles(&mut self, memory_manager: &mut MemoryManager) {
 
         let bg_window_tile_data: u16;
         let tile_map_display: u16;
-        let tile_y: u8;
 
-        let scroll_y = self.memory_manager.borrow_mut().read_memory(0xFF42);
-        let scroll_x = self.memory_manager.borrow_mut().read_memory(0xFF43);
-        let window_y = self.memory_manager.borrow_mut().read_memory(0xFF4A);
-        let window_x = self.memory_manager.borrow_mut().read_memory(0xFF4B).wrapping_sub(7);
+        let scroll_y = memory_manager.read_memory(0xFF42);
+        let scroll_x = memory_manager.read_memory(0xFF43);
+        let window_y = memory_manager.read_memory(0xFF4A);
+        let window_x = memory_manager.read_memory(0xFF4B).wrapping_sub(7);
         let mut window_enabled = false;
         let mut unsigned_data = true;
 
-        if self.test_display_bit(5) && window_y <= self.memory_manager.borrow_mut().read_memory(0xFF44) {
+        if self.test_display_bit(5, memory_manager) && window_y <= memory_manager.read_memory(0xFF44) {
             window_enabled = true;
         }
 
-        if self.test_display_bit(4) {
+        if self.test_display_bit(4, memory_manager) {
             bg_window_tile_data = 0x8000;
         }
         else {
@@ -119,7 +101,7 @@ impl DisplayManager {
         }
 
         if !window_enabled {
-            if self.test_display_bit(3) {
+            if self.test_display_bit(3, memory_manager) {
                 tile_map_display = 0x9C00;
             }
             else {
@@ -127,7 +109,7 @@ impl DisplayManager {
             }
         }
         else {
-            if self.test_display_bit(6) {
+            if self.test_display_bit(6, memory_manager) {
                 tile_map_display = 0x9C00;
             }
             else {
@@ -135,12 +117,12 @@ impl DisplayManager {
             }
         }
 
-        if !window_enabled {
-            tile_y = scroll_y.wrapping_add(self.memory_manager.borrow_mut().read_memory(0xFF44));
+        let tile_y = if !window_enabled {
+            scroll_y.wrapping_add(memory_manager.read_memory(0xFF44))
         }
         else {
-            tile_y = self.memory_manager.borrow_mut().read_memory(0xFF44).wrapping_sub(window_y);
-        }
+            memory_manager.read_memory(0xFF44).wrapping_sub(window_y)
+        };
 
         let pixel_y = (tile_y as u16 / 8) * 32;
 
@@ -149,15 +131,13 @@ impl DisplayManager {
         for pixel in 0..160 {
             let tile_x = if window_enabled && pixel >= window_x { pixel.wrapping_sub(window_x) } else { scroll_x.wrapping_add(pixel) };
             let pixel_x = tile_x as u16 / 8;
-            let tile_address = tile_map_display + pixel_x as u16 + pixel_y as u16;
-            let tile_id: i16;
-
-            if unsigned_data {
-                tile_id = (self.memory_manager.borrow_mut().read_memory(tile_address) as u8) as i16;
+            let tile_address = tile_map_display + pixel_x + pixel_y;
+            let tile_id = if unsigned_data {
+                memory_manager.read_memory(tile_address) as i16
             }
             else {
-                tile_id = (self.memory_manager.borrow_mut().read_memory(tile_address) as i8) as i16;
-            }
+                (memory_manager.read_memory(tile_address) as i8) as i16
+            };
             
             let mut tile_loc = bg_window_tile_data;
             if unsigned_data {
@@ -168,89 +148,66 @@ impl DisplayManager {
             }
 
             let current_line = (tile_y % 8) * 2;
-            let line_data_lo = self.memory_manager.borrow_mut().read_memory(tile_loc + current_line as u16);
-            let line_data_hi = self.memory_manager.borrow_mut().read_memory(tile_loc + current_line as u16 + 1);
-            let color_loc = ((tile_x as i32 % 8) - 7) * -1;
+            let line_data_lo = memory_manager.read_memory(tile_loc + current_line as u16);
+            let line_data_hi = memory_manager.read_memory(tile_loc + current_line as u16 + 1);
+            let color_loc = 7 - (tile_x as i32 % 8);
             let mut color_id = if (line_data_hi & (1 << color_loc)) >> color_loc == 1 { 1 << 1 } else { 0 };
             color_id |= if (line_data_lo & (1 << color_loc)) >> color_loc == 1 { 1 } else { 0 };
-            let color = self.get_color(color_id, 0xFF47);
-            let red: u8;
-            let green: u8;
-            let blue: u8;
-
-            match color {
-                DisplayColor::White => { red = 0xFF; green = 0xFF; blue = 0xFF },
-                DisplayColor::LightGray => { red = 0xCC; green = 0xCC; blue = 0xCC },
-                DisplayColor::DarkGray => { red = 0x77; green = 0x77; blue = 0x77 },
-                DisplayColor::Black => { red = 0x00; green = 0x00; blue = 0x00 }
-            }
+            let color = self.get_color(color_id, 0xFF47, memory_manager);
 
-            let current_scanline = self.memory_manager.borrow_mut().read_memory(0xFF44);
+            let current_scanline = memory_manager.read_memory(0xFF44);
             if current_scanline <= 143 && pixel <= 159 {
-                self.display[pixel as usize][current_scanline as usize][0] = red;
-                self.display[pixel as usize][current_scanline as usize][1] = green;
-                self.display[pixel as usize][current_scanline as usize][2] = blue;
+                self.display[current_scanline as usize * SCREEN_WIDTH + pixel as usize] = color.get_shade();
             }
         }
     }
 
     /// Adds the current sprites in memeory
     /// to the display.
-    pub fn render_sprites(&mut self) {
+    pub fn render_sprites(&mut self, memory_manager: &mut MemoryManager) {
 
         for current_sprite in 0..40 {
-            let sprite_y = self.memory_manager.borrow_mut().read_memory(0xFE00 + (current_sprite * 4)) as u16 as i32 - 16;
-            let sprite_x = self.memory_manager.borrow_mut().read_memory(0xFE00 + (current_sprite * 4) + 1) as u16 as i32 - 8;
-            let sprite_id = self.memory_manager.borrow_mut().read_memory(0xFE00 + (current_sprite * 4) + 2) as u16;
-            let sprite_attrs = self.memory_manager.borrow_mut().read_memory(0xFE00 + (current_sprite * 4) + 3);
-            let current_scanline = self.memory_manager.borrow_mut().read_memory(0xFF44) as i32;
+            let sprite_y = memory_manager.read_memory(0xFE00 + (current_sprite * 4)) as u16 as i32 - 16;
+            let sprite_x = memory_manager.read_memory(0xFE00 + (current_sprite * 4) + 1) as u16 as i32 - 8;
+            let sprite_id = memory_manager.read_memory(0xFE00 + (current_sprite * 4) + 2) as u16;
+            let sprite_attrs = memory_manager.read_memory(0xFE00 + (current_sprite * 4) + 3);
+            let current_scanline = memory_manager.read_memory(0xFF44) as i32;
 
-            let flip_x = if (sprite_attrs & (1 << 5)) >> 5 == 1 { true } else { false }; 
-            let flip_y = if (sprite_attrs & (1 << 6)) >> 6 == 1 { true } else { false }; 
-            let sprite_size = if self.test_display_bit(2) { 16 } else { 8 };
+            let flip_x = (sprite_attrs & (1 << 5)) != 0;
+            let flip_y = (sprite_attrs & (1 << 6)) != 0;
+            let sprite_size = if self.test_display_bit(2, memory_manager) { 16 } else { 8 };
 
             if current_scanline >= sprite_y && current_scanline < (sprite_y + sprite_size) {
                 
                 let sprite_line = if flip_y {
-                    (((current_scanline - sprite_y) - sprite_size) * -1) as u16
+                    (sprite_size - (current_scanline - sprite_y)) as u16
                 }
                 else {
                     (current_scanline - sprite_y) as u16
                 };
 
                 let data_address = 0x8000 + sprite_id * 16 + sprite_line * 2;
-                let data_lo = self.memory_manager.borrow_mut().read_memory(data_address);
-                let data_hi = self.memory_manager.borrow_mut().read_memory(data_address + 1);
+                let data_lo = memory_manager.read_memory(data_address);
+                let data_hi = memory_manager.read_memory(data_address + 1);
 
                 for sprite_pixel in 0..8 {
 
-                    let mut color_loc = if flip_x { (sprite_pixel - 7) * -1 } else { sprite_pixel };
+                    let color_loc = if flip_x { 7 - sprite_pixel } else { sprite_pixel };
                     let mut color_id = if (data_hi & (1 << color_loc)) != 0 { 1 << 1 } else { 0 };
                     color_id |= if (data_lo & (1 << color_loc)) != 0 { 1 } else { 0 };
 
                     // Don't print pixels with a color ID of 0, they're transparent.
                     if color_id == 0 { continue };
                     let color_address = if (sprite_attrs & (1 << 4)) >> 4 == 1 { 0xFF49 } else { 0xFF48 };
-                    let mut color = self.get_color(color_id, color_address);
-
-                    let red: u8;
-                    let green: u8;
-                    let blue: u8;
-
-                    match color {
-                        DisplayColor::White => { red = 0xFF; green = 0xFF; blue = 0xFF },
-                        DisplayColor::LightGray => { red = 0xCC; green = 0xCC; blue = 0xCC },
-                        DisplayColor::DarkGray => { red = 0x77; green = 0x77; blue = 0x77 },
-                        DisplayColor::Black => { red = 0x00; green = 0x00; blue = 0x00 }
-                    }
+                    let color = self.get_color(color_id, color_address, memory_manager);
 
                     let pixel = sprite_x + (7 - sprite_pixel);
 
-                    if (current_scanline <= 143 && current_scanline >= 0 && pixel <= 159 && pixel >= 0) &&
-                       ((sprite_attrs & (1 << 7)) >> 7 != 1 || self.display[pixel as usize][current_scanline as usize][0] == 0xFF) {
-                        self.display[pixel as usize][current_scanline as usize][0] = red;
-                        self.display[pixel as usize][current_scanline as usize][1] = green;
-                        self.display[pixel as usize][current_scanline as usize][2] = blue;
+                    if (0..=143).contains(&current_scanline) && (0..=159).contains(&pixel) {
+                        let index = current_scanline as usize * SCREEN_WIDTH + pixel as usize;
+                        if (sprite_attrs & (1 << 7)) >> 7 != 1 || self.display[index] == 0 {
+                            self.display[index] = color.get_shade();
+                        }
                     }
                 }
             }
@@ -258,71 +215,82 @@ impl DisplayManager {
     }
 
     /// Draws the current scanline.
-    pub fn draw_scanline(&mut self) {
-        let display_control = self.memory_manager.borrow_mut().read_memory(0xFF40);
+    pub fn draw_scanline(&mut self, memory_manager: &mut MemoryManager) {
+        let display_control = memory_manager.read_memory(0xFF40);
 
         if display_control & 1 == 1 {
-            self.render_tiles();
+            self.render_tiles(memory_manager);
         }
         if (display_control & (1 << 1)) >> 1 == 1 {
-            self.render_sprites();
+            self.render_sprites(memory_manager);
         }
     }
 
     /// Determines whether the scanline
-    /// needs to be updated.
-    pub fn update_display(&mut self, cycles: i32) {
-        self.set_display_status();
+    /// needs to be updated. Returns true
+    /// when V-Blank has just been entered.
+    pub fn update_display(&mut self, cycles: i32, memory_manager: &mut MemoryManager) -> bool {
+        self.set_display_status(memory_manager);
         
         // Update remaining cycles only if 
         // the display is enabled
-        if self.test_display_bit(7) {
+        if self.test_display_bit(7, memory_manager) {
             self.remaining_cycles -= cycles;
         }
         else {
-            return;
+            return false;
         }
 
         // Move to next scanline
+        let mut entered_vblank = false;
         if self.remaining_cycles <= 0 {
-            self.memory_manager.borrow_mut().memory[0xFF44] += 1;
-            let next_scanline = self.memory_manager.borrow_mut().read_memory(0xFF44);
-            self.remaining_cycles = 456;
+            memory_manager.memory[0xFF44] += 1;
+            let next_scanline = memory_manager.read_memory(0xFF44);
+
+            // Carry over extra cycles so lines
+            // stay 456 cycles long
+            self.remaining_cycles += 456;
 
             // V-Blank
             if next_scanline == 144 {
-                self.memory_manager.borrow_mut().request_interrupt(0);
+                memory_manager.request_interrupt(0);
+                self.frame_count += 1;
+                self.frame = Frame::from_shades(&self.display, self.frame_count);
+                entered_vblank = true;
             }
 
-            // Reset scanline
+            // Reset scanline, drawing line 0
+            // as the next frame starts
             else if next_scanline > 153 {
-                self.memory_manager.borrow_mut().memory[0xFF44] = 0;
+                memory_manager.memory[0xFF44] = 0;
+                self.draw_scanline(memory_manager);
             }
 
             else if next_scanline < 144 {
-                self.draw_scanline();
+                self.draw_scanline(memory_manager);
             }
         }
+        entered_vblank
     }
 
     /// Determines what the current status of display is and
     /// updates the current mode if necessary.
-    pub fn set_display_status(&mut self) {
-        let mut display_status = self.memory_manager.borrow_mut().read_memory(0xFF41);
+    pub fn set_display_status(&mut self, memory_manager: &mut MemoryManager) {
+        let mut display_status = memory_manager.read_memory(0xFF41);
 
         // Test if display is enabled
-        if !self.test_display_bit(7) {
+        if !self.test_display_bit(7, memory_manager) {
             self.remaining_cycles = 456;
-            self.memory_manager.borrow_mut().memory[0xFF44] = 0;
+            memory_manager.memory[0xFF44] = 0;
             display_status &= 0xFC;
             display_status |= 1;
-            self.memory_manager.borrow_mut().write_memory(0xFF41, display_status);
+            memory_manager.write_memory(0xFF41, display_status);
             return;
         }
 
         let previous_mode = display_status & 0x3;
         let new_mode: u8;
-        let current_scanline = self.memory_manager.borrow_mut().read_memory(0xFF44);
+        let current_scanline = memory_manager.read_memory(0xFF44);
         let mut request_interrupt = false;
 
         // V-Blank
@@ -373,25 +341,31 @@ impl DisplayManager {
         }
 
         if request_interrupt && (new_mode != previous_mode) {
-            self.memory_manager.borrow_mut().request_interrupt(1);
+            memory_manager.request_interrupt(1);
         }
 
-        if current_scanline == self.memory_manager.borrow_mut().read_memory(0xFF45) {
+        if current_scanline == memory_manager.read_memory(0xFF45) {
             display_status |= 1 << 2;
             if (display_status & (1 << 6) >> 6) == 1 {
-                self.memory_manager.borrow_mut().request_interrupt(1);
+                memory_manager.request_interrupt(1);
             }
         }
         else {
             // Set bit 2 to 0
             display_status &= 0xFB;
         }
-        self.memory_manager.borrow_mut().write_memory(0xFF41, display_status);
+        memory_manager.write_memory(0xFF41, display_status);
     }
 
     /// Tests a bit at 0xFF40 to determine if the 
     /// whether the bit is on.
-    pub fn test_display_bit(&mut self, bit: u8) -> bool {
-        if (self.memory_manager.borrow_mut().read_memory(0xFF40) & (1 << bit)) >> bit == 1 { true } else { false }
+    pub fn test_display_bit(&mut self, bit: u8, memory_manager: &mut MemoryManager) -> bool {
+        (memory_manager.read_memory(0xFF40) & (1 << bit)) != 0
+    }
+}
+
+impl Default for DisplayManager {
+    fn default() -> DisplayManager {
+        DisplayManager::new()
     }
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors that stop the emulator, along with
+/// the state needed to report what went wrong.
+#[derive(Debug)]
+pub enum EmulatorError {
+    Io(io::Error),
+    InvalidRomSize { size: usize },
+    InvalidBootRomSize { size: usize },
+    UnsupportedCartridgeType { cartridge_type: u8 },
+    UndefinedOpcode { pc: u16, opcode: u8 },
+    InvalidInterrupt { bit: u8 },
+    InvalidBankingAddress { address: u16, byte: u8 },
+    RomBankOutOfRange { bank: u16, address: u16 },
+    InvalidImageSize { width: u32, height: u32, length: usize }
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmulatorError::Io(ref e) => write!(f, "{}", e),
+            EmulatorError::InvalidRomSize { size } => {
+                write!(f, "Invalid ROM size, {} bytes", size)
+            },
+            EmulatorError::InvalidBootRomSize { size } => {
+                write!(f, "Invalid boot ROM size, {} bytes instead of 256", size)
+            },
+            EmulatorError::UnsupportedCartridgeType { cartridge_type } => {
+                write!(f, "Banking type is currently not supported. Value at 0x147 was 0x{:02X}", cartridge_type)
+            },
+            EmulatorError::UndefinedOpcode { pc, opcode } => {
+                write!(f, "Undefined opcode 0x{:02X} at 0x{:04X}", opcode, pc)
+            },
+            EmulatorError::InvalidInterrupt { bit } => {
+                write!(f, "Invalid bit given to interrupt handler: {}", bit)
+            },
+            EmulatorError::InvalidBankingAddress { address, byte } => {
+                write!(f, "Attempted to write 0x{:02X} to inaccessible banking address 0x{:04X}", byte, address)
+            },
+            EmulatorError::RomBankOutOfRange { bank, address } => {
+                write!(f, "ROM bank {} is out of range when reading 0x{:04X}", bank, address)
+            },
+            EmulatorError::InvalidImageSize { width, height, length } => {
+                write!(f, "Invalid image size, {} bytes of RGB data for {}x{} pixels", length, width, height)
+            }
+        }
+    }
+}
+
+impl Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EmulatorError::Io(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for EmulatorError {
+    fn from(e: io::Error) -> EmulatorError {
+        EmulatorError::Io(e)
+    }
+}
\ No newline at end of file
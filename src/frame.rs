@@ -0,0 +1,79 @@
+/// Width of the display in pixels.
+pub const SCREEN_WIDTH: usize = 160;
+
+/// Height of the display in pixels.
+pub const SCREEN_HEIGHT: usize = 144;
+
+/// RGB values for each of the four shades,
+/// from white to black.
+pub const SHADE_RGB: [[u8; 3]; 4] = [
+    [0xFF, 0xFF, 0xFF],
+    [0xCC, 0xCC, 0xCC],
+    [0x77, 0x77, 0x77],
+    [0x00, 0x00, 0x00]
+];
+
+/// A completed frame of the display.
+#[derive(Clone)]
+pub struct Frame {
+    shades: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    number: u64
+}
+
+impl Frame {
+
+    /// Default constructor, for a blank
+    /// white frame.
+    pub fn new() -> Frame {
+        Frame {
+            shades: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            number: 0
+        }
+    }
+
+    /// Creates a frame from row-major shades.
+    pub fn from_shades(shades: &[u8; SCREEN_WIDTH * SCREEN_HEIGHT], number: u64) -> Frame {
+        Frame {
+            shades: *shades,
+            number
+        }
+    }
+
+    /// Returns the number of frames the display
+    /// had completed when this one finished.
+    pub fn get_number(&self) -> u64 {
+        self.number
+    }
+
+    /// Returns the shade of every pixel in row-major
+    /// order, from 0 for white to 3 for black.
+    pub fn get_shades(&self) -> &[u8] {
+        &self.shades
+    }
+
+    /// Returns the shade of the pixel at x, y.
+    pub fn get_shade(&self, x: usize, y: usize) -> u8 {
+        self.shades[y * SCREEN_WIDTH + x]
+    }
+
+    /// Returns the RGB value of the pixel at x, y.
+    pub fn get_rgb(&self, x: usize, y: usize) -> [u8; 3] {
+        SHADE_RGB[self.get_shade(x, y) as usize]
+    }
+
+    /// Returns the frame as row-major RGBA8 bytes.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.shades.len() * 4);
+        for shade in self.shades.iter() {
+            rgba.extend_from_slice(&SHADE_RGB[*shade as usize]);
+            rgba.push(0xFF);
+        }
+        rgba
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Frame {
+        Frame::new()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,170 @@
+use frame::*;
+
+use std::collections::VecDeque;
+
+/// The state of the Gameboy's eight
+/// buttons, where true means pressed.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct ButtonState {
+    pub right: bool,
+    pub left: bool,
+    pub up: bool,
+    pub down: bool,
+    pub a: bool,
+    pub b: bool,
+    pub select: bool,
+    pub start: bool
+}
+
+impl ButtonState {
+
+    /// Returns the buttons ordered by the
+    /// key numbers used by the gamepad.
+    pub fn as_array(&self) -> [bool; 8] {
+        [self.right, self.left, self.up, self.down, self.a, self.b, self.select, self.start]
+    }
+
+    /// Sets a button by its gamepad key number.
+    pub fn set_key(&mut self, key: i32, pressed: bool) {
+        match key {
+            0 => self.right = pressed,
+            1 => self.left = pressed,
+            2 => self.up = pressed,
+            3 => self.down = pressed,
+            4 => self.a = pressed,
+            5 => self.b = pressed,
+            6 => self.select = pressed,
+            7 => self.start = pressed,
+            _ => {}
+        }
+    }
+}
+
+/// Receives each frame the display completes.
+pub trait VideoSink {
+
+    /// Called with each completed 160x144 frame.
+    fn draw_frame(&mut self, frame: &Frame);
+}
+
+/// Receives the sound produced by the emulator.
+pub trait AudioSink {
+
+    /// Called with interleaved left and right
+    /// samples in the range -1.0 to 1.0.
+    fn queue_samples(&mut self, samples: &[f32]);
+}
+
+/// Supplies the state of the buttons.
+pub trait InputSource {
+
+    /// Returns the current button state, or
+    /// None once the source has been closed.
+    fn poll_buttons(&mut self) -> Option<ButtonState>;
+}
+
+/// Video sink that discards every frame.
+pub struct NullVideoSink;
+
+impl VideoSink for NullVideoSink {
+    fn draw_frame(&mut self, _frame: &Frame) {}
+}
+
+/// Audio sink that discards every sample.
+pub struct NullAudioSink;
+
+impl AudioSink for NullAudioSink {
+    fn queue_samples(&mut self, _samples: &[f32]) {}
+}
+
+/// Input source that never presses
+/// a button and never closes.
+pub struct NullInputSource;
+
+impl InputSource for NullInputSource {
+    fn poll_buttons(&mut self) -> Option<ButtonState> {
+        Some(ButtonState::default())
+    }
+}
+
+/// Video sink that keeps the most recent
+/// frame and counts the frames drawn.
+pub struct FrameRecorder {
+    pub last_frame: Frame,
+    pub frames_drawn: u64
+}
+
+impl FrameRecorder {
+
+    /// Default constructor.
+    pub fn new() -> FrameRecorder {
+        FrameRecorder {
+            last_frame: Frame::new(),
+            frames_drawn: 0
+        }
+    }
+}
+
+impl Default for FrameRecorder {
+    fn default() -> FrameRecorder {
+        FrameRecorder::new()
+    }
+}
+
+impl VideoSink for FrameRecorder {
+    fn draw_frame(&mut self, frame: &Frame) {
+        self.last_frame = frame.clone();
+        self.frames_drawn += 1;
+    }
+}
+
+/// Audio sink that stores every sample
+/// it receives.
+pub struct SampleRecorder {
+    pub samples: Vec<f32>
+}
+
+impl SampleRecorder {
+
+    /// Default constructor.
+    pub fn new() -> SampleRecorder {
+        SampleRecorder {
+            samples: Vec::new()
+        }
+    }
+}
+
+impl Default for SampleRecorder {
+    fn default() -> SampleRecorder {
+        SampleRecorder::new()
+    }
+}
+
+impl AudioSink for SampleRecorder {
+    fn queue_samples(&mut self, samples: &[f32]) {
+        self.samples.extend_from_slice(samples);
+    }
+}
+
+/// Input source that plays back one button
+/// state per poll and closes when it runs out.
+pub struct ScriptedInputSource {
+    states: VecDeque<ButtonState>
+}
+
+impl ScriptedInputSource {
+
+    /// Creates a source that returns the
+    /// given states in order.
+    pub fn new(states: Vec<ButtonState>) -> ScriptedInputSource {
+        ScriptedInputSource {
+            states: states.into_iter().collect()
+        }
+    }
+}
+
+impl InputSource for ScriptedInputSource {
+    fn poll_buttons(&mut self) -> Option<ButtonState> {
+        self.states.pop_front()
+    }
+}
\ No newline at end of file
@@ -1,69 +1,224 @@
-pub extern crate sdl2;
-
+use cartridge::*;
 use cpu::*;
+use error::*;
+use frame::*;
+use frontend::*;
 use memory_manager::*;
 use interrupt_handler::*;
 use display_manager::*;
 use gamepad::*;
+use serial::*;
+
+/// Number of cycles the display takes
+/// to draw a frame.
+pub const CYCLES_PER_FRAME: i32 = 70224;
+
+/// Why the emulator stopped running.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+
+    /// A single instruction was executed.
+    InstructionComplete,
+
+    /// The requested number of cycles ran. Also
+    /// returned by run_frame when the display is
+    /// off and no V-Blank occurred within a frame.
+    CyclesElapsed,
+
+    /// The display entered V-Blank (LY=144).
+    VBlank
+}
 
-use std::cell::RefCell;
-use std::rc::Rc;
-use sdl2::EventPump;
+/// The outcome of running the emulator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunResult {
+    pub cycles: i32,
+    pub reason: StopReason
+}
 
 pub struct Gameboy {
     pub cpu: Cpu,
-    pub memory_manager: Rc<RefCell<MemoryManager>>,
+    pub memory_manager: MemoryManager,
     pub interrupt_handler: InterruptHandler,
     pub display_manager: DisplayManager,
-    pub gamepad: Gamepad,
-    pub event_pump: EventPump
+    pub gamepad: Gamepad
 }
 
 impl Gameboy {
 
-    /// Default constructor.
-    pub fn new() -> Gameboy {
+    /// Creates a Gameboy with the given
+    /// cartridge inserted.
+    pub fn new(cartridge: Cartridge) -> Gameboy {
+        Gameboy {
+            memory_manager: MemoryManager::new(cartridge),
+            cpu: Cpu::new(),
+            interrupt_handler: InterruptHandler::new(),
+            display_manager: DisplayManager::new(),
+            gamepad: Gamepad::new()
+        }
+    }
 
-        // SDL2 tools
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
-        let event_pump = sdl_context.event_pump().unwrap();
+    /// Creates a Gameboy that runs the given DMG or MGB
+    /// boot ROM from 0x0000, starting from zeroed state.
+    pub fn with_boot_rom(cartridge: Cartridge, boot_rom: &[u8]) -> Result<Gameboy, EmulatorError> {
+        if boot_rom.len() != 0x100 {
+            return Err(EmulatorError::InvalidBootRomSize { size: boot_rom.len() });
+        }
 
-        let memory_manager = Rc::new(RefCell::new(MemoryManager::new()));
-        let cpu = Cpu::new(Rc::clone(&memory_manager));
-        let interrupt_handler = InterruptHandler::new(Rc::clone(&memory_manager));
-        let display_manager = DisplayManager::new(Rc::clone(&memory_manager), &video_subsystem);
-        let gamepad = Gamepad::new(Rc::clone(&memory_manager));
+        Ok(Gameboy {
+            memory_manager: MemoryManager::with_boot_rom(cartridge, boot_rom.to_vec()),
+            cpu: Cpu::new_for_boot_rom(),
+            interrupt_handler: InterruptHandler::new(),
+            display_manager: DisplayManager::new(),
+            gamepad: Gamepad::new()
+        })
+    }
 
-        Gameboy {
-            memory_manager: memory_manager,
-            cpu: cpu,
-            interrupt_handler: interrupt_handler,
-            display_manager: display_manager,
-            gamepad: gamepad,
-            event_pump: event_pump
+    /// Executes a single instruction and advances the
+    /// timers, display and interrupts to match. Any
+    /// interrupt dispatched afterwards is included in
+    /// the cycles. Stops with VBlank if the display
+    /// entered V-Blank.
+    pub fn step_instruction(&mut self) -> Result<RunResult, EmulatorError> {
+
+        // STOP halts the clock, leaving the timers and display
+        // frozen until a selected joypad line goes low
+        if self.cpu.is_stopped() {
+            if (self.memory_manager.read_memory(0xFF00) & 0x0F) != 0x0F {
+                self.cpu.set_stopped(false);
+            }
+            return Ok(RunResult {
+                cycles: 4,
+                reason: StopReason::InstructionComplete
+            });
+        }
+
+        let mut cycles = self.cpu.interpret_opcode(&mut self.memory_manager)?;
+        let mut entered_vblank = self.update_components(cycles);
+
+        let dispatch_cycles = self.interrupt_handler.check_interrupts(&mut self.cpu, &mut self.memory_manager)?;
+        if dispatch_cycles > 0 {
+            entered_vblank |= self.update_components(dispatch_cycles);
+            cycles += dispatch_cycles;
         }
+
+        if let Some(fault) = self.memory_manager.take_fault() {
+            return Err(fault);
+        }
+
+        Ok(RunResult {
+            cycles,
+            reason: if entered_vblank { StopReason::VBlank } else { StopReason::InstructionComplete }
+        })
     }
 
-    /// Runs a single frame's worth of
-    /// CPU cycles. Returns false when
-    /// the emulation has stopped.
-    pub fn step(&mut self) -> bool {
-        let max_cycles = 69905;
-        let mut cycles_per_step = 0;
+    /// Advances the timers, audio, serial port and display
+    /// by the given cycles. Returns whether the display
+    /// entered V-Blank.
+    fn update_components(&mut self, cycles: i32) -> bool {
+        self.memory_manager.update_timers(cycles);
+        self.memory_manager.update_audio(cycles);
+        self.memory_manager.update_serial(cycles);
+        self.display_manager.update_display(cycles, &mut self.memory_manager)
+    }
 
-        if !self.gamepad.poll_events(&mut self.event_pump) {
-            return false;
+    /// Runs whole instructions until at least the
+    /// given number of cycles have elapsed.
+    pub fn run_cycles(&mut self, cycles: i32) -> Result<RunResult, EmulatorError> {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step_instruction()?.cycles;
         }
 
-        while cycles_per_step < max_cycles {
-            let current_cycles = self.cpu.interpret_opcode();
-            cycles_per_step += current_cycles;
-            self.memory_manager.borrow_mut().update_timers(current_cycles);
-            self.display_manager.update_display(current_cycles);
-            self.interrupt_handler.check_interrupts(&mut self.cpu);
+        Ok(RunResult {
+            cycles: elapsed,
+            reason: StopReason::CyclesElapsed
+        })
+    }
+
+    /// Runs until the display enters V-Blank, stopping
+    /// after the instruction that reached LY=144.
+    pub fn run_frame(&mut self) -> Result<RunResult, EmulatorError> {
+        let mut elapsed = 0;
+
+        // The display never reaches V-Blank while it's
+        // off, so give up after a frame's worth of cycles
+        while elapsed < CYCLES_PER_FRAME {
+            let result = self.step_instruction()?;
+            elapsed += result.cycles;
+            if result.reason == StopReason::VBlank {
+                return Ok(RunResult {
+                    cycles: elapsed,
+                    reason: StopReason::VBlank
+                });
+            }
+        }
+
+        Ok(RunResult {
+            cycles: elapsed,
+            reason: StopReason::CyclesElapsed
+        })
+    }
+
+    /// Getter for the last frame the
+    /// display completed.
+    pub fn get_frame(&self) -> &Frame {
+        self.display_manager.get_frame()
+    }
+
+    /// Getter for the number of frames
+    /// the display has completed.
+    pub fn get_frame_count(&self) -> u64 {
+        self.display_manager.get_frame_count()
+    }
+
+    /// Takes the audio samples produced since the
+    /// last call, interleaved left and right at
+    /// the APU's sample rate.
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.memory_manager.take_audio_samples()
+    }
+
+    /// Writes battery backed cartridge RAM to
+    /// its save file if it has changed.
+    pub fn save(&mut self) -> Result<(), EmulatorError> {
+        self.memory_manager.save_ram()
+    }
+
+    /// Connects a peer, such as another Game Boy
+    /// or a printer, to the link port.
+    pub fn set_link_peer(&mut self, peer: Box<dyn LinkPeer>) {
+        self.memory_manager.set_link_peer(peer);
+    }
+
+    /// Getter for whether the cartridge's
+    /// rumble motor is on.
+    pub fn is_rumbling(&self) -> bool {
+        self.memory_manager.is_rumbling()
+    }
+
+    /// Sets a function to be called with the new
+    /// state whenever the rumble motor turns on or
+    /// off, so frontends can vibrate a controller.
+    pub fn set_rumble_callback(&mut self, callback: Option<Box<dyn FnMut(bool) + Send>>) {
+        self.memory_manager.set_rumble_callback(callback);
+    }
+
+    /// Polls the input source and runs until the
+    /// next V-Blank, then hands the frame to the
+    /// video sink. Returns false when the input
+    /// source has been closed.
+    pub fn step(&mut self, video: &mut dyn VideoSink, audio: &mut dyn AudioSink, input: &mut dyn InputSource) -> Result<bool, EmulatorError> {
+        match input.poll_buttons() {
+            Some(state) => self.gamepad.set_state(state, &mut self.memory_manager),
+            None => return Ok(false)
         }
-        self.display_manager.draw_display();
-        true
+
+        self.run_frame()?;
+        video.draw_frame(self.display_manager.get_frame());
+
+        let samples = self.memory_manager.take_audio_samples();
+        audio.queue_samples(&samples);
+        Ok(true)
     }
 }
\ No newline at end of file
@@ -1,105 +1,71 @@
 use memory_manager::*;
-
-use std::rc::Rc;
-use std::cell::RefCell;
-use sdl2::EventPump;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use frontend::*;
 
 pub struct Gamepad {
-    memory_manager: Rc<RefCell<MemoryManager>>,
+    state: ButtonState
 }
 
 impl Gamepad {
-    pub fn new(memory_manager: Rc<RefCell<MemoryManager>>) -> Gamepad {
+    pub fn new() -> Gamepad {
         Gamepad {
-            memory_manager: memory_manager,
+            state: ButtonState::default()
         }
     }
 
-    /// Returns an integer to identify 
-    /// the keycode.
-    pub fn resolve_key(&mut self, key: Keycode) -> Option<i32> {
-        match key {
-            Keycode::D => Some(0),
-            Keycode::A => Some(1),
-            Keycode::W => Some(2),
-            Keycode::S => Some(3),
-            Keycode::K => Some(4),
-            Keycode::J => Some(5),
-            Keycode::Space => Some(6),
-            Keycode::Return => Some(7),
-            _ => None
-        }
-    }
+    /// Presses and releases keys so the gamepad
+    /// matches the given button state.
+    pub fn set_state(&mut self, state: ButtonState, memory_manager: &mut MemoryManager) {
+        let previous = self.state.as_array();
+        let current = state.as_array();
 
-    /// Polls the current events in the
-    /// event pump and return false
-    /// when the quit event occurs.
-    pub fn poll_events(&mut self, event_pump: &mut EventPump) -> bool {
-
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    return false;
-                },
-                Event::KeyDown { keycode: Some(keycode), repeat, .. } => {
-                    if !repeat {
-                        let key_val = self.resolve_key(keycode);
-                        if key_val != None {
-                            self.key_pressed(key_val.unwrap());
-                        }
-                    }
-                },
-                Event::KeyUp { keycode: Some(keycode), .. } => {
-                    let key_val = self.resolve_key(keycode);
-                    if key_val != None {
-                        self.key_released(key_val.unwrap());
-                    }                
-                },
-                _ => {}
+        for key in 0..8 {
+            if current[key] && !previous[key] {
+                self.key_pressed(key as i32, memory_manager);
+            }
+            else if !current[key] && previous[key] {
+                self.key_released(key as i32, memory_manager);
             }
         }
-        true
+        self.state = state;
     }
 
     /// Updates the gamepad's state when
     /// a key is released.
-    pub fn key_released(&mut self, key: i32) {
-        let new_state = self.memory_manager.borrow_mut().gamepad_state | (1 << key);
-        self.memory_manager.borrow_mut().gamepad_state = new_state;
+    pub fn key_released(&mut self, key: i32, memory_manager: &mut MemoryManager) {
+        let new_state = memory_manager.gamepad_state | (1 << key);
+        memory_manager.gamepad_state = new_state;
     }
 
     /// Updates the gamepad state and requests
     /// the necessary interrupt when a key is pressed.
-    pub fn key_pressed(&mut self, key: i32) {
+    pub fn key_pressed(&mut self, key: i32, memory_manager: &mut MemoryManager) {
 
         // Check if the key was not set already
         let mut state_changed = true;
 
-        if (self.memory_manager.borrow_mut().gamepad_state & (1 << key)) >> key == 0 {
+        if (memory_manager.gamepad_state & (1 << key)) >> key == 0 {
             state_changed = false;
         }
 
-        self.memory_manager.borrow_mut().gamepad_state ^= 1 << key;
+        memory_manager.gamepad_state ^= 1 << key;
 
         // Check if the key is a button or 
         // direction
-        let key_is_button = if key > 3 { true } else { false };
+        let key_is_button = key > 3;
 
         // Determine if an interrupt is needed
-        let gamepad_reg = self.memory_manager.borrow_mut().read_memory(0xFF00);
-        let mut will_request_interrupt = false;
-        
-        if key_is_button && ((gamepad_reg & (1 << 5)) >> 5) == 0 {
-            will_request_interrupt = true;
-        }
-        else if !key_is_button && ((gamepad_reg & (1 << 4)) >> 4) == 0 {
-            will_request_interrupt = true;
-        }
+        let gamepad_reg = memory_manager.read_memory(0xFF00);
+        let select_bit = if key_is_button { 5 } else { 4 };
+        let will_request_interrupt = (gamepad_reg & (1 << select_bit)) == 0;
 
         if will_request_interrupt && state_changed {
-            self.memory_manager.borrow_mut().request_interrupt(4);
+            memory_manager.request_interrupt(4);
         }
     }
+}
+
+impl Default for Gamepad {
+    fn default() -> Gamepad {
+        Gamepad::new()
+    }
 }
\ No newline at end of file
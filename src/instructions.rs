@@ -1,5 +1,3 @@
-use register_pair::*;
-
 pub fn test_bit(byte: u8, bit: u8) -> bool {
     if (byte & (1 << bit)) >> bit == 1 {
         return true;
@@ -7,32 +5,336 @@ pub fn test_bit(byte: u8, bit: u8) -> bool {
     false
 }
 
-pub fn inc_reg_pair(dest: &mut RegisterPair) {
-    let prev_pair = dest.get_pair();
-    dest.set_pair(prev_pair.wrapping_add(1));
+pub fn swap_nybbles(byte: &mut u8) {
+    *byte = (*byte & 0xF) << 4 | (*byte >> 4);
 }
 
-pub fn dec_reg_pair(dest: &mut RegisterPair) {
-    let prev_pair = dest.get_pair();
-    dest.set_pair(prev_pair.wrapping_sub(1));
+pub fn set_bit(byte: &mut u8, bit: u8) {
+    *byte |= 1 << bit;
 }
 
-pub fn ld_u16_reg_pair(src: u16, dest: &mut RegisterPair) {
-    dest.set_pair(src);
+pub fn reset_bit(byte: &mut u8, bit: u8) {
+    *byte &= !(1 << bit);
 }
 
-pub fn ld_u8_reg(src: u8, dest: &mut u8) {
-    *dest = src;
+/// An 8-bit operand, in the order opcodes encode
+/// them. IndirectHL is the byte at the address in HL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reg8 {
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    IndirectHL,
+    A
 }
 
-pub fn swap_nybbles(byte: &mut u8) {
-    *byte = (*byte & 0xF) << 4 | (*byte >> 4);
+/// A register pair used by 16-bit loads and arithmetic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reg16 {
+    BC,
+    DE,
+    HL,
+    SP
 }
 
-pub fn set_bit(byte: &mut u8, bit: u8) {
-    *byte |= 1 << bit;
+/// A register pair used by PUSH and POP, where
+/// AF takes the place of SP.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reg16Stack {
+    BC,
+    DE,
+    HL,
+    AF
 }
 
-pub fn reset_bit(byte: &mut u8, bit: u8) {
-    *byte &= !(1 << bit);
-}
\ No newline at end of file
+/// A memory operand addressed by a register pair
+/// for loads to and from A. HL can be incremented
+/// or decremented afterwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Indirect {
+    BC,
+    DE,
+    HLIncrement,
+    HLDecrement
+}
+
+/// A flag condition for jumps, calls and returns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    NotZero,
+    Zero,
+    NotCarry,
+    Carry
+}
+
+/// An arithmetic or logic operation on A.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AluOp {
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp
+}
+
+/// A rotate, shift or swap from the CB prefixed opcodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShiftOp {
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl
+}
+
+/// A decoded instruction along with its operands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    Nop,
+    Stop,
+    Halt,
+    Di,
+    Ei,
+
+    // 8-bit loads
+    Ld8 { dest: Reg8, src: Reg8 },
+    Ld8Immediate { dest: Reg8, value: u8 },
+    LdIndirectA(Indirect),
+    LdAIndirect(Indirect),
+    LdAddressA(u16),
+    LdAAddress(u16),
+    LdhAddressA(u8),
+    LdhAAddress(u8),
+    LdhCA,
+    LdhAC,
+
+    // 16-bit loads
+    Ld16Immediate { dest: Reg16, value: u16 },
+    LdAddressSp(u16),
+    LdSpHl,
+    LdHlSpOffset(i8),
+    Push(Reg16Stack),
+    Pop(Reg16Stack),
+
+    // Arithmetic and logic
+    Inc8(Reg8),
+    Dec8(Reg8),
+    Inc16(Reg16),
+    Dec16(Reg16),
+    AddHl(Reg16),
+    AddSpOffset(i8),
+    Alu { op: AluOp, src: Reg8 },
+    AluImmediate { op: AluOp, value: u8 },
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+
+    // Rotates on A, which always clear the zero flag
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+
+    // Control flow
+    Jp { condition: Option<Condition>, address: u16 },
+    JpHl,
+    Jr { condition: Option<Condition>, offset: i8 },
+    Call { condition: Option<Condition>, address: u16 },
+    Ret { condition: Option<Condition> },
+    Reti,
+    Rst(u8),
+
+    // CB prefixed
+    Shift { op: ShiftOp, target: Reg8 },
+    Bit { bit: u8, target: Reg8 },
+    Res { bit: u8, target: Reg8 },
+    Set { bit: u8, target: Reg8 },
+
+    // Opcodes with no instruction, which lock up the CPU
+    Undefined(u8)
+}
+
+static REG8_TABLE: [Reg8; 8] = [Reg8::B, Reg8::C, Reg8::D, Reg8::E, Reg8::H, Reg8::L, Reg8::IndirectHL, Reg8::A];
+static REG16_TABLE: [Reg16; 4] = [Reg16::BC, Reg16::DE, Reg16::HL, Reg16::SP];
+static REG16_STACK_TABLE: [Reg16Stack; 4] = [Reg16Stack::BC, Reg16Stack::DE, Reg16Stack::HL, Reg16Stack::AF];
+static INDIRECT_TABLE: [Indirect; 4] = [Indirect::BC, Indirect::DE, Indirect::HLIncrement, Indirect::HLDecrement];
+static CONDITION_TABLE: [Condition; 4] = [Condition::NotZero, Condition::Zero, Condition::NotCarry, Condition::Carry];
+static ALU_TABLE: [AluOp; 8] = [AluOp::Add, AluOp::Adc, AluOp::Sub, AluOp::Sbc, AluOp::And, AluOp::Xor, AluOp::Or, AluOp::Cp];
+static SHIFT_TABLE: [ShiftOp; 8] = [ShiftOp::Rlc, ShiftOp::Rrc, ShiftOp::Rl, ShiftOp::Rr, ShiftOp::Sla, ShiftOp::Sra, ShiftOp::Swap, ShiftOp::Srl];
+
+/// Decodes an instruction from a stream of bytes, calling
+/// next_byte once for the opcode and once for each byte
+/// that follows it. Opcodes are split into the fields
+/// x (bits 6-7), y (bits 3-5) and z (bits 0-2), which
+/// select the operation and its operands.
+pub fn decode<F: FnMut() -> u8>(mut next_byte: F) -> Instruction {
+    let opcode = next_byte();
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 0x07) as usize;
+    let z = (opcode & 0x07) as usize;
+    let p = y >> 1;
+
+    match x {
+        0 => match z {
+            0 => match y {
+                0 => Instruction::Nop,
+                1 => Instruction::LdAddressSp(next_word(&mut next_byte)),
+                2 => {
+                    next_byte();
+                    Instruction::Stop
+                },
+                3 => Instruction::Jr { condition: None, offset: next_byte() as i8 },
+                _ => Instruction::Jr { condition: Some(CONDITION_TABLE[y - 4]), offset: next_byte() as i8 }
+            },
+            1 => {
+                if (y & 1) == 0 {
+                    Instruction::Ld16Immediate { dest: REG16_TABLE[p], value: next_word(&mut next_byte) }
+                }
+                else {
+                    Instruction::AddHl(REG16_TABLE[p])
+                }
+            },
+            2 => {
+                if (y & 1) == 0 {
+                    Instruction::LdIndirectA(INDIRECT_TABLE[p])
+                }
+                else {
+                    Instruction::LdAIndirect(INDIRECT_TABLE[p])
+                }
+            },
+            3 => {
+                if (y & 1) == 0 {
+                    Instruction::Inc16(REG16_TABLE[p])
+                }
+                else {
+                    Instruction::Dec16(REG16_TABLE[p])
+                }
+            },
+            4 => Instruction::Inc8(REG8_TABLE[y]),
+            5 => Instruction::Dec8(REG8_TABLE[y]),
+            6 => Instruction::Ld8Immediate { dest: REG8_TABLE[y], value: next_byte() },
+            _ => match y {
+                0 => Instruction::Rlca,
+                1 => Instruction::Rrca,
+                2 => Instruction::Rla,
+                3 => Instruction::Rra,
+                4 => Instruction::Daa,
+                5 => Instruction::Cpl,
+                6 => Instruction::Scf,
+                _ => Instruction::Ccf
+            }
+        },
+        1 => {
+            if y == 6 && z == 6 {
+                Instruction::Halt
+            }
+            else {
+                Instruction::Ld8 { dest: REG8_TABLE[y], src: REG8_TABLE[z] }
+            }
+        },
+        2 => Instruction::Alu { op: ALU_TABLE[y], src: REG8_TABLE[z] },
+        _ => match opcode {
+            0xC0 | 0xC8 | 0xD0 | 0xD8 => Instruction::Ret { condition: Some(CONDITION_TABLE[y]) },
+            0xC2 | 0xCA | 0xD2 | 0xDA => Instruction::Jp { condition: Some(CONDITION_TABLE[y]), address: next_word(&mut next_byte) },
+            0xC4 | 0xCC | 0xD4 | 0xDC => Instruction::Call { condition: Some(CONDITION_TABLE[y]), address: next_word(&mut next_byte) },
+            0xC1 | 0xD1 | 0xE1 | 0xF1 => Instruction::Pop(REG16_STACK_TABLE[p]),
+            0xC5 | 0xD5 | 0xE5 | 0xF5 => Instruction::Push(REG16_STACK_TABLE[p]),
+            0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => Instruction::AluImmediate { op: ALU_TABLE[y], value: next_byte() },
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => Instruction::Rst((y as u8) * 8),
+            0xC3 => Instruction::Jp { condition: None, address: next_word(&mut next_byte) },
+            0xC9 => Instruction::Ret { condition: None },
+            0xCB => decode_extended(next_byte()),
+            0xCD => Instruction::Call { condition: None, address: next_word(&mut next_byte) },
+            0xD9 => Instruction::Reti,
+            0xE0 => Instruction::LdhAddressA(next_byte()),
+            0xE2 => Instruction::LdhCA,
+            0xE8 => Instruction::AddSpOffset(next_byte() as i8),
+            0xE9 => Instruction::JpHl,
+            0xEA => Instruction::LdAddressA(next_word(&mut next_byte)),
+            0xF0 => Instruction::LdhAAddress(next_byte()),
+            0xF2 => Instruction::LdhAC,
+            0xF3 => Instruction::Di,
+            0xF8 => Instruction::LdHlSpOffset(next_byte() as i8),
+            0xF9 => Instruction::LdSpHl,
+            0xFA => Instruction::LdAAddress(next_word(&mut next_byte)),
+            0xFB => Instruction::Ei,
+            _ => Instruction::Undefined(opcode)
+        }
+    }
+}
+
+/// Decodes the opcode following a CB prefix.
+fn decode_extended(opcode: u8) -> Instruction {
+    let y = (opcode >> 3) & 0x07;
+    let target = REG8_TABLE[(opcode & 0x07) as usize];
+    match opcode >> 6 {
+        0 => Instruction::Shift { op: SHIFT_TABLE[y as usize], target },
+        1 => Instruction::Bit { bit: y, target },
+        2 => Instruction::Res { bit: y, target },
+        _ => Instruction::Set { bit: y, target }
+    }
+}
+
+/// Reads a little endian word from the stream.
+fn next_word<F: FnMut() -> u8>(next_byte: &mut F) -> u16 {
+    let byte_lo = next_byte() as u16;
+    let byte_hi = next_byte() as u16;
+    (byte_hi << 8) | byte_lo
+}
+
+impl Instruction {
+
+    /// Returns the number of cycles the instruction takes.
+    /// Conditional instructions take longer when their
+    /// branch is taken, which is ignored for the rest.
+    pub fn get_cycles(&self, branch_taken: bool) -> i32 {
+        let indirect = |reg: &Reg8| *reg == Reg8::IndirectHL;
+        match *self {
+            Instruction::Nop | Instruction::Stop | Instruction::Halt | Instruction::Di | Instruction::Ei => 4,
+            Instruction::Ld8 { ref dest, ref src } => if indirect(dest) || indirect(src) { 8 } else { 4 },
+            Instruction::Ld8Immediate { ref dest, .. } => if indirect(dest) { 12 } else { 8 },
+            Instruction::LdIndirectA(_) | Instruction::LdAIndirect(_) => 8,
+            Instruction::LdAddressA(_) | Instruction::LdAAddress(_) => 16,
+            Instruction::LdhAddressA(_) | Instruction::LdhAAddress(_) => 12,
+            Instruction::LdhCA | Instruction::LdhAC => 8,
+            Instruction::Ld16Immediate { .. } => 12,
+            Instruction::LdAddressSp(_) => 20,
+            Instruction::LdSpHl => 8,
+            Instruction::LdHlSpOffset(_) => 12,
+            Instruction::Push(_) => 16,
+            Instruction::Pop(_) => 12,
+            Instruction::Inc8(ref target) | Instruction::Dec8(ref target) => if indirect(target) { 12 } else { 4 },
+            Instruction::Inc16(_) | Instruction::Dec16(_) | Instruction::AddHl(_) => 8,
+            Instruction::AddSpOffset(_) => 16,
+            Instruction::Alu { ref src, .. } => if indirect(src) { 8 } else { 4 },
+            Instruction::AluImmediate { .. } => 8,
+            Instruction::Daa | Instruction::Cpl | Instruction::Scf | Instruction::Ccf => 4,
+            Instruction::Rlca | Instruction::Rrca | Instruction::Rla | Instruction::Rra => 4,
+            Instruction::Jp { condition: None, .. } => 16,
+            Instruction::Jp { .. } => if branch_taken { 16 } else { 12 },
+            Instruction::JpHl => 4,
+            Instruction::Jr { condition: None, .. } => 12,
+            Instruction::Jr { .. } => if branch_taken { 12 } else { 8 },
+            Instruction::Call { condition: None, .. } => 24,
+            Instruction::Call { .. } => if branch_taken { 24 } else { 12 },
+            Instruction::Ret { condition: None } => 16,
+            Instruction::Ret { .. } => if branch_taken { 20 } else { 8 },
+            Instruction::Reti | Instruction::Rst(_) => 16,
+            Instruction::Bit { ref target, .. } => if indirect(target) { 12 } else { 8 },
+            Instruction::Shift { ref target, .. } | Instruction::Res { ref target, .. } | Instruction::Set { ref target, .. } => {
+                if indirect(target) { 16 } else { 8 }
+            },
+            Instruction::Undefined(_) => 4
+        }
+    }
+}
@@ -1,60 +1,85 @@
 use memory_manager::*;
 use cpu::*;
+use error::*;
 
-use std::rc::Rc;
-use std::cell::RefCell;
+/// Number of cycles taken to dispatch an interrupt.
+pub const INTERRUPT_DISPATCH_CYCLES: i32 = 20;
 
-pub struct InterruptHandler {
-    memory_manager: Rc<RefCell<MemoryManager>>
-}
+pub struct InterruptHandler;
 
 impl InterruptHandler {
 
     /// Default constructor.
-    pub fn new(memory_manager: Rc<RefCell<MemoryManager>>) -> InterruptHandler {
-        InterruptHandler {
-            memory_manager: memory_manager
+    pub fn new() -> InterruptHandler {
+        InterruptHandler
+    }
+
+    /// Returns the address of the handler
+    /// for an interrupt's bit.
+    pub fn get_vector(bit: u8) -> Result<u16, EmulatorError> {
+        match bit {
+            0 => Ok(0x40),    // V-Blank
+            1 => Ok(0x48),    // LCD
+            2 => Ok(0x50),    // Timer
+            3 => Ok(0x58),    // Serial
+            4 => Ok(0x60),    // Controller
+            _ => Err(EmulatorError::InvalidInterrupt { bit })
         }
     }
 
-    /// Handles an interrupt for a given bit.
-    pub fn handle_interrupt(&mut self, bit: u8, cpu: &mut Cpu) {
-        if cpu.get_interrupts_enabled() {
-            cpu.set_interrupts_enabled(false);
-            let mut request_value = self.memory_manager.borrow_mut().read_memory(0xFF0F);
-            request_value ^= 1 << bit;
-            self.memory_manager.borrow_mut().write_memory(0xFF0F, request_value);
-
-            // Push PC onto stack
-            let pc = cpu.get_reg_pc();
-            cpu.stack_push(pc);
-
-            match bit {
-                0 => cpu.set_reg_pc(0x40),    // V-Blank
-                1 => cpu.set_reg_pc(0x48),    // LCD
-                2 => cpu.set_reg_pc(0x50),    // Timer
-                4 => cpu.set_reg_pc(0x60),    // Controller
-                _ => panic!("Invalid bit given to interrupt handler: {}", bit)
-            }
+    /// Returns the bits of interrupts that
+    /// are both requested and enabled.
+    pub fn get_pending(memory_manager: &mut MemoryManager) -> u8 {
+        memory_manager.read_memory(0xFF0F) & memory_manager.read_memory(0xFFFF) & 0x1F
+    }
+
+    /// Dispatches the highest priority pending interrupt.
+    /// The interrupt is picked after the upper byte of PC
+    /// is pushed, so a push that overwrites IE can change
+    /// it, or cancel dispatch and jump to 0x0000 instead.
+    pub fn handle_interrupt(&mut self, cpu: &mut Cpu, memory_manager: &mut MemoryManager) -> Result<(), EmulatorError> {
+        cpu.set_interrupts_enabled(false);
+        cpu.cancel_halt_bug();
+
+        let pc = cpu.get_reg_pc();
+        cpu.stack_push_byte((pc >> 8) as u8, memory_manager);
+        let pending = InterruptHandler::get_pending(memory_manager);
+        cpu.stack_push_byte((pc & 0xFF) as u8, memory_manager);
+
+        if pending == 0 {
+            cpu.set_reg_pc(0x0000);
+            return Ok(());
         }
+
+        // Lower bits have higher priority
+        let bit = pending.trailing_zeros() as u8;
+        let request_value = memory_manager.read_memory(0xFF0F);
+        memory_manager.write_memory(0xFF0F, request_value & !(1 << bit));
+        cpu.set_reg_pc(InterruptHandler::get_vector(bit)?);
+        Ok(())
     }
 
-    /// Checks if any interrupts need to be handled.
-    pub fn check_interrupts(&mut self, cpu: &mut Cpu) {
-        let request_value = self.memory_manager.borrow_mut().read_memory(0xFF0F);
-        let enabled_value = self.memory_manager.borrow_mut().read_memory(0xFFFF);
-
-        if request_value > 0 {
-            for i in 0..5 {
-
-                // Check if request and enabled registers 
-                // are set to 1
-                if (request_value & (1 << i)) >> i == 1 && (enabled_value & (1 << i)) >> i == 1 {
-                    self.handle_interrupt(i, cpu);
-                    cpu.set_halted(false);
-                }
-            }
+    /// Checks if an interrupt needs to be handled, waking
+    /// the CPU if it's halted. Returns the number of cycles
+    /// taken to dispatch it, or 0 if none was.
+    pub fn check_interrupts(&mut self, cpu: &mut Cpu, memory_manager: &mut MemoryManager) -> Result<i32, EmulatorError> {
+        if InterruptHandler::get_pending(memory_manager) == 0 {
+            return Ok(0);
         }
+
+        cpu.set_halted(false);
+        if !cpu.get_interrupts_enabled() {
+            return Ok(0);
+        }
+
+        self.handle_interrupt(cpu, memory_manager)?;
+        Ok(INTERRUPT_DISPATCH_CYCLES)
     }
 
-}
\ No newline at end of file
+}
+
+impl Default for InterruptHandler {
+    fn default() -> InterruptHandler {
+        InterruptHandler::new()
+    }
+}
@@ -0,0 +1,28 @@
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+pub mod gameboy;
+pub mod cartridge;
+pub mod cartridge_header;
+pub mod real_time_clock;
+pub mod cpu;
+pub mod register_pair;
+pub mod memory_manager;
+pub mod display_manager;
+pub mod interrupt_handler;
+pub mod gamepad;
+pub mod timer;
+pub mod serial;
+pub mod link_cable;
+pub mod printer;
+pub mod png;
+pub mod apu;
+pub mod resampler;
+pub mod instructions;
+pub mod disasm;
+pub mod static_disasm;
+pub mod error;
+pub mod frame;
+pub mod frontend;
+#[cfg(feature = "sdl")]
+pub mod sdl_frontend;
\ No newline at end of file
@@ -0,0 +1,213 @@
+use error::*;
+use serial::*;
+
+#[cfg(unix)]
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Sent by the side driving the clock,
+/// carrying the byte it shifts out.
+static MESSAGE_TRANSFER: u8 = 0x01;
+
+/// Sent back by the side on the external
+/// clock, carrying the byte it shifted out.
+static MESSAGE_REPLY: u8 = 0x02;
+
+/// How long to wait for the other Game Boy to answer
+/// a transfer before treating the cable as unplugged.
+static REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Prefix marking an address as a Unix domain socket path.
+static UNIX_PREFIX: &str = "unix:";
+
+/// A link cable to another emulator over a socket. Every
+/// message is three bytes: its kind, a sequence number
+/// matching replies to transfers, and the data byte.
+pub struct SocketLinkPeer {
+    writer: Box<dyn Write + Send>,
+    messages: Receiver<[u8; 3]>,
+    sequence: u8,
+    connected: bool,
+    // Sequence number of the transfer waiting
+    // for a reply, and when to give up on it
+    pending: Option<(u8, Instant)>
+}
+
+impl SocketLinkPeer {
+
+    /// Creates a link from the two halves of a connected
+    /// socket. Messages are read on their own thread so
+    /// the emulator never blocks waiting for the peer.
+    fn new<R: Read + Send + 'static>(mut reader: R, writer: Box<dyn Write + Send>) -> SocketLinkPeer {
+        let (sender, messages) = channel();
+        thread::spawn(move || {
+            let mut message = [0; 3];
+            while reader.read_exact(&mut message).is_ok() {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        SocketLinkPeer {
+            writer,
+            messages,
+            sequence: 0,
+            connected: true,
+            pending: None
+        }
+    }
+
+    /// Creates a link over a connected TCP stream.
+    pub fn from_tcp(stream: TcpStream) -> Result<SocketLinkPeer, EmulatorError> {
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        Ok(SocketLinkPeer::new(reader, Box::new(stream)))
+    }
+
+    /// Creates a link over a connected Unix domain socket.
+    #[cfg(unix)]
+    pub fn from_unix(stream: UnixStream) -> Result<SocketLinkPeer, EmulatorError> {
+        let reader = stream.try_clone()?;
+        Ok(SocketLinkPeer::new(reader, Box::new(stream)))
+    }
+
+    /// Connects to another emulator listening at address,
+    /// which is either host:port or unix:path.
+    pub fn connect(address: &str) -> Result<SocketLinkPeer, EmulatorError> {
+        #[cfg(unix)]
+        {
+            if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+                return SocketLinkPeer::from_unix(UnixStream::connect(path)?);
+            }
+        }
+        SocketLinkPeer::from_tcp(TcpStream::connect(address)?)
+    }
+
+    /// Listens at address, which is either host:port or
+    /// unix:path, and waits for another emulator to connect.
+    pub fn listen(address: &str) -> Result<SocketLinkPeer, EmulatorError> {
+        #[cfg(unix)]
+        {
+            if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+
+                // Remove a socket left behind by an earlier
+                // run, which would otherwise fail the bind
+                if let Ok(metadata) = fs::symlink_metadata(path) {
+                    if metadata.file_type().is_socket() {
+                        fs::remove_file(path)?;
+                    }
+                }
+                let listener = UnixListener::bind(path)?;
+                let (stream, _) = listener.accept()?;
+                return SocketLinkPeer::from_unix(stream);
+            }
+        }
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        SocketLinkPeer::from_tcp(stream)
+    }
+
+    /// Getter for whether the other
+    /// emulator is still connected.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Sends a message, dropping the
+    /// connection if it fails.
+    fn send(&mut self, kind: u8, sequence: u8, byte: u8) {
+        if self.writer.write_all(&[kind, sequence, byte]).and_then(|_| self.writer.flush()).is_err() {
+            self.connected = false;
+        }
+    }
+}
+
+impl LinkPeer for SocketLinkPeer {
+    fn start_transfer(&mut self, outgoing: u8) {
+        if !self.connected {
+            self.pending = None;
+            return;
+        }
+
+        self.sequence = self.sequence.wrapping_add(1);
+        let sequence = self.sequence;
+        self.send(MESSAGE_TRANSFER, sequence, outgoing);
+        self.pending = Some((sequence, Instant::now() + REPLY_TIMEOUT));
+    }
+
+    fn poll_transfer(&mut self) -> Option<u8> {
+        let (sequence, deadline) = match self.pending {
+            Some(pending) => pending,
+            None => return Some(0xFF)
+        };
+
+        while self.connected {
+            match self.messages.try_recv() {
+                Ok([kind, reply_sequence, byte]) => {
+                    if kind == MESSAGE_REPLY && reply_sequence == sequence {
+                        self.pending = None;
+                        return Some(byte);
+                    }
+
+                    // Both sides are driving the clock, so neither
+                    // is listening. Answer as an idle line would.
+                    if kind == MESSAGE_TRANSFER {
+                        self.send(MESSAGE_REPLY, reply_sequence, 0xFF);
+                    }
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.connected = false
+            }
+        }
+
+        // Keep the transfer open until the reply arrives, shifting
+        // in an idle line if the peer is gone or never answers
+        if self.connected && Instant::now() < deadline {
+            return None;
+        }
+        self.pending = None;
+        Some(0xFF)
+    }
+
+    fn poll_external(&mut self, outgoing: u8) -> Option<u8> {
+        while self.connected {
+            match self.messages.try_recv() {
+                Ok([kind, sequence, byte]) => {
+
+                    // Replies to transfers that
+                    // timed out are dropped
+                    if kind == MESSAGE_TRANSFER {
+                        self.send(MESSAGE_REPLY, sequence, outgoing);
+                        return Some(byte);
+                    }
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.connected = false
+            }
+        }
+        None
+    }
+
+    fn poll_idle(&mut self, outgoing: u8) {
+        while self.connected {
+            match self.messages.try_recv() {
+                Ok([kind, sequence, _]) => {
+                    if kind == MESSAGE_TRANSFER {
+                        self.send(MESSAGE_REPLY, sequence, outgoing);
+                    }
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.connected = false
+            }
+        }
+    }
+}
@@ -1,23 +1,269 @@
-pub mod gameboy;
-pub mod cartridge;
-pub mod cpu;
-pub mod register_pair;
-pub mod memory_manager;
-pub mod display_manager;
-pub mod interrupt_handler;
-pub mod gamepad;
-pub mod instructions;
-
-use gameboy::*;
+extern crate rusty_boy_dmg;
+extern crate sdl2;
 
+use rusty_boy_dmg::cartridge::*;
+use rusty_boy_dmg::error::*;
+use rusty_boy_dmg::frontend::*;
+use rusty_boy_dmg::gameboy::*;
+use rusty_boy_dmg::link_cable::*;
+use rusty_boy_dmg::printer::*;
+use rusty_boy_dmg::sdl_frontend::*;
+use rusty_boy_dmg::static_disasm::*;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
 use std::time::Duration;
 
+/// Seconds of audio to keep queued when
+/// pacing emulation from the audio queue.
+static AUDIO_SYNC_TARGET: f64 = 0.05;
+
+/// Number of steps between writes of battery backed
+/// RAM to the save file. Each step is about a frame,
+/// even while the LCD is off and no frames complete.
+static SAVE_INTERVAL: u64 = 60;
+
+/// Options given on the command line.
+struct Options {
+    rom_path: String,
+    boot_rom_path: Option<String>,
+    link_listen: Option<String>,
+    link_connect: Option<String>,
+    printer_dir: Option<String>,
+    sample_rate: i32,
+    audio_sync: bool,
+    mute: bool
+}
+
+/// Prints the usage message and exits.
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} [--sample-rate <44100|48000>] [--audio-sync] [--mute] [--boot-rom <file>] [--link-listen <address>] [--link-connect <address>] [--printer <dir>] <rom>", program);
+    eprintln!("       {} disasm <rom> <output dir>", program);
+    process::exit(1);
+}
+
+/// Disassembles a ROM into an RGBDS
+/// project for the disasm subcommand.
+fn disassemble(args: &[String]) {
+    if args.len() != 4 {
+        usage(&args[0]);
+    }
+
+    let result = fs::read(&args[2]).map_err(EmulatorError::from).and_then(|rom| {
+        let analysis = RomAnalysis::analyze(&rom)?;
+        analysis.write_project(Path::new(&args[3]))?;
+        Ok(analysis)
+    });
+    match result {
+        Ok(analysis) => {
+            println!("Disassembled {} banks with {} bytes of code into {}", analysis.get_bank_count(), analysis.get_code_size(), args[3]);
+        },
+        Err(e) => {
+            eprintln!("Failed to disassemble {}: {}", args[2], e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Parses the command line arguments.
+fn parse_options(args: &[String]) -> Options {
+    let mut options = Options {
+        rom_path: String::new(),
+        boot_rom_path: None,
+        link_listen: None,
+        link_connect: None,
+        printer_dir: None,
+        sample_rate: 48000,
+        audio_sync: false,
+        mute: false
+    };
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--sample-rate" => {
+                i += 1;
+                options.sample_rate = match args.get(i).and_then(|rate| rate.parse().ok()) {
+                    Some(rate) => rate,
+                    None => usage(&args[0])
+                };
+            },
+            "--boot-rom" => {
+                i += 1;
+                options.boot_rom_path = match args.get(i) {
+                    Some(path) => Some(path.clone()),
+                    None => usage(&args[0])
+                };
+            },
+            "--link-listen" => {
+                i += 1;
+                options.link_listen = match args.get(i) {
+                    Some(address) => Some(address.clone()),
+                    None => usage(&args[0])
+                };
+            },
+            "--link-connect" => {
+                i += 1;
+                options.link_connect = match args.get(i) {
+                    Some(address) => Some(address.clone()),
+                    None => usage(&args[0])
+                };
+            },
+            "--printer" => {
+                i += 1;
+                options.printer_dir = match args.get(i) {
+                    Some(dir) => Some(dir.clone()),
+                    None => usage(&args[0])
+                };
+            },
+            "--audio-sync" => options.audio_sync = true,
+            "--mute" => options.mute = true,
+            arg if arg.starts_with("--") => usage(&args[0]),
+            arg => options.rom_path = arg.to_string()
+        }
+        i += 1;
+    }
+
+    // Only one thing can be plugged into the link port
+    let link_options = [options.link_listen.is_some(), options.link_connect.is_some(), options.printer_dir.is_some()];
+    if options.rom_path.is_empty() || link_options.iter().filter(|set| **set).count() > 1 {
+        usage(&args[0]);
+    }
+    options
+}
+
+/// Writes battery backed RAM to the save
+/// file, reporting any failure.
+fn save(gameboy: &mut Gameboy) {
+    if let Err(e) = gameboy.save() {
+        eprintln!("Failed to write save file: {}", e);
+    }
+}
+
 fn main() {
-    let mut gameboy = Gameboy::new();
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(|arg| arg.as_str()) == Some("disasm") {
+        disassemble(&args);
+        return;
+    }
+    let options = parse_options(&args);
+
+    let cartridge = match Cartridge::from_path(Path::new(&options.rom_path)) {
+        Ok(cartridge) => cartridge,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", options.rom_path, e);
+            process::exit(1);
+        }
+    };
+
+    // The boot ROM refuses to start a cartridge
+    // with a bad header checksum, so warn about it
+    let header = cartridge.get_header().clone();
+    if !header.header_checksum_valid {
+        eprintln!("Warning: {} has an invalid header checksum", options.rom_path);
+    }
+    let title = if header.title.is_empty() {
+        String::from("Rusty Boy DMG")
+    }
+    else {
+        format!("Rusty Boy DMG - {}", header.title)
+    };
+
+    // SDL2 tools
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let event_pump = sdl_context.event_pump().unwrap();
+
+    let mut video = SdlVideoSink::new(&video_subsystem, &title);
+    let mut input = SdlInputSource::new(event_pump);
+
+    // Fall back to silence if audio can't be opened
+    let mut sdl_audio = None;
+    if !options.mute {
+        match sdl_context.audio().and_then(|audio_subsystem| SdlAudioSink::new(&audio_subsystem, options.sample_rate)) {
+            Ok(audio) => sdl_audio = Some(audio),
+            Err(e) => eprintln!("Failed to open audio, continuing without sound: {}", e)
+        }
+    }
+    let mut null_audio = NullAudioSink;
+
+    let mut gameboy = match options.boot_rom_path {
+        Some(ref path) => {
+            match fs::read(path).map_err(|e| e.into()).and_then(|boot_rom| Gameboy::with_boot_rom(cartridge, &boot_rom)) {
+                Ok(gameboy) => gameboy,
+                Err(e) => {
+                    eprintln!("Failed to load boot ROM {}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+        },
+        None => Gameboy::new(cartridge)
+    };
+
+    // Connect the link cable before starting so
+    // neither side misses the first transfer
+    let link = if let Some(ref address) = options.link_listen {
+        println!("Waiting for link cable connection on {}", address);
+        Some((address, SocketLinkPeer::listen(address)))
+    }
+    else {
+        options.link_connect.as_ref().map(|address| (address, SocketLinkPeer::connect(address)))
+    };
+    match link {
+        Some((_, Ok(peer))) => gameboy.set_link_peer(Box::new(peer)),
+        Some((address, Err(e))) => {
+            eprintln!("Failed to connect link cable at {}: {}", address, e);
+            process::exit(1);
+        },
+        None => {}
+    }
+
+    if let Some(ref dir) = options.printer_dir {
+        let mut printer = Printer::new(PathBuf::from(dir));
+        printer.set_print_callback(Some(Box::new(|result| {
+            match result {
+                Ok(path) => println!("Printed to {}", path.display()),
+                Err(e) => eprintln!("Failed to print: {}", e)
+            }
+        })));
+        gameboy.set_link_peer(Box::new(printer));
+    }
+    let mut steps_since_save = 0;
     loop {
-        if !gameboy.step() {
-            break;
+        let result = match sdl_audio {
+            Some(ref mut audio) => gameboy.step(&mut video, audio, &mut input),
+            None => gameboy.step(&mut video, &mut null_audio, &mut input)
+        };
+
+        match result {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => {
+                eprintln!("Emulation stopped: {}", e);
+                save(&mut gameboy);
+                process::exit(1);
+            }
+        }
+
+        steps_since_save += 1;
+        if steps_since_save >= SAVE_INTERVAL {
+            save(&mut gameboy);
+            steps_since_save = 0;
+        }
+
+        // Wait for the audio queue to drain when syncing to
+        // audio, otherwise sleep for a 60th of a second
+        match sdl_audio {
+            Some(ref audio) if options.audio_sync => {
+                while audio.get_queued_seconds() > AUDIO_SYNC_TARGET {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            },
+            _ => thread::sleep(Duration::new(0, 1_000_000_000u32 / 60))
         }
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
+    save(&mut gameboy);
 }
\ No newline at end of file
@@ -1,88 +1,115 @@
 use cartridge::*;
-
-static TIMER: u16 = 0xFF05;
-static TIMER_MODULATOR: u16 = 0xFF06;
-static TIMER_CONTROLLER: u16 = 0xFF07;
+use apu::*;
+use serial::*;
+use timer::*;
+use error::*;
 
 pub struct MemoryManager {
     cartridge: Cartridge,
+    apu: Apu,
+    serial: Serial,
     pub memory: [u8; 0x10000],
-    timer_counter: i32,
-    div_counter: i32,
+    timer: Timer,
+    fault: Option<EmulatorError>,
+    boot_rom: Option<Vec<u8>>,
     pub gamepad_state: u8
 }
 
 impl MemoryManager {
 
-    /// Default constructor.
-    pub fn new() -> MemoryManager {
+    /// Default constructor, for the state
+    /// left behind by the boot ROM.
+    pub fn new(cartridge: Cartridge) -> MemoryManager {
+        let mut manager = MemoryManager::build(cartridge, None);
+        manager.set_post_boot_state();
+        manager
+    }
+
+    /// Creates a memory manager with a 256 byte boot ROM
+    /// mapped over 0x0000-0x00FF until 0xFF50 is written.
+    /// Everything else starts cleared, as at power on.
+    pub fn with_boot_rom(cartridge: Cartridge, boot_rom: Vec<u8>) -> MemoryManager {
+        MemoryManager::build(cartridge, Some(boot_rom))
+    }
+
+    /// Creates a memory manager with the
+    /// cartridge's first two banks loaded.
+    fn build(cartridge: Cartridge, boot_rom: Option<Vec<u8>>) -> MemoryManager {
         let mut memory = [0; 0x10000];
-        let mut cartridge = Cartridge::new();
-        for i in 0..0x8000 {
-            memory[i] = cartridge.get_rom(i as u32);
+        let mut cartridge = cartridge;
+        for (i, byte) in memory.iter_mut().enumerate().take(0x8000) {
+            *byte = cartridge.get_rom(i as u32);
         }
-        let mut manager = MemoryManager {
-            cartridge: cartridge,
-            memory: memory,
-            timer_counter: 1024,
-            div_counter: 0,
+        MemoryManager {
+            cartridge,
+            apu: Apu::new(),
+            serial: Serial::new(),
+            memory,
+            timer: Timer::new(),
+            fault: None,
+            boot_rom,
             gamepad_state: 0xFF
-        };
-
-        // Initial startup sequence
-        manager.memory[0xFF05] = 0x00; 
-        manager.memory[0xFF06] = 0x00; 
-        manager.memory[0xFF07] = 0x00; 
-        manager.memory[0xFF10] = 0x80; 
-        manager.memory[0xFF11] = 0xBF; 
-        manager.memory[0xFF12] = 0xF3; 
-        manager.memory[0xFF14] = 0xBF; 
-        manager.memory[0xFF16] = 0x3F; 
-        manager.memory[0xFF17] = 0x00; 
-        manager.memory[0xFF19] = 0xBF; 
-        manager.memory[0xFF1A] = 0x7F; 
-        manager.memory[0xFF1B] = 0xFF; 
-        manager.memory[0xFF1C] = 0x9F; 
-        manager.memory[0xFF1E] = 0xBF; 
-        manager.memory[0xFF20] = 0xFF; 
-        manager.memory[0xFF21] = 0x00; 
-        manager.memory[0xFF22] = 0x00; 
-        manager.memory[0xFF23] = 0xBF; 
-        manager.memory[0xFF24] = 0x77; 
-        manager.memory[0xFF25] = 0xF3;
-        manager.memory[0xFF26] = 0xF1; 
-        manager.memory[0xFF40] = 0x91; 
-        manager.memory[0xFF42] = 0x00; 
-        manager.memory[0xFF43] = 0x00; 
-        manager.memory[0xFF45] = 0x00; 
-        manager.memory[0xFF47] = 0xFC; 
-        manager.memory[0xFF48] = 0xFF; 
-        manager.memory[0xFF49] = 0xFF; 
-        manager.memory[0xFF4A] = 0x00; 
-        manager.memory[0xFF4B] = 0x00; 
-        manager.memory[0xFFFF] = 0x00; 
+        }
+    }
 
-        manager
+    /// Sets the IO registers to the values
+    /// the boot ROM leaves them with.
+    fn set_post_boot_state(&mut self) {
+        self.timer.set_system_counter(0xABCC);
+        self.memory[0xFF40] = 0x91; 
+        self.memory[0xFF42] = 0x00; 
+        self.memory[0xFF43] = 0x00; 
+        self.memory[0xFF45] = 0x00; 
+        self.memory[0xFF47] = 0xFC; 
+        self.memory[0xFF48] = 0xFF; 
+        self.memory[0xFF49] = 0xFF; 
+        self.memory[0xFF4A] = 0x00; 
+        self.memory[0xFF4B] = 0x00; 
+        self.memory[0xFFFF] = 0x00; 
+
+        // Sound registers go through the APU, which has to be
+        // powered on first. The trigger bit is left out of
+        // NRx4 so no channel starts playing.
+        self.write_memory(0xFF26, 0xF1);
+        self.write_memory(0xFF10, 0x80);
+        self.write_memory(0xFF11, 0xBF);
+        self.write_memory(0xFF12, 0xF3);
+        self.write_memory(0xFF14, 0x3F);
+        self.write_memory(0xFF16, 0x3F);
+        self.write_memory(0xFF17, 0x00);
+        self.write_memory(0xFF19, 0x3F);
+        self.write_memory(0xFF1A, 0x7F);
+        self.write_memory(0xFF1B, 0xFF);
+        self.write_memory(0xFF1C, 0x9F);
+        self.write_memory(0xFF1E, 0x3F);
+        self.write_memory(0xFF20, 0xFF);
+        self.write_memory(0xFF21, 0x00);
+        self.write_memory(0xFF22, 0x00);
+        self.write_memory(0xFF23, 0x3F);
+        self.write_memory(0xFF24, 0x77);
+        self.write_memory(0xFF25, 0xF3);
     }
 
-    /// Returns the clock frequency using the
-    /// first 2 bits of the timer controller.
-    pub fn get_frequency(&mut self) -> u8 {
-        self.read_memory(TIMER_CONTROLLER) & 0x03
+    /// Returns whether the boot ROM is
+    /// still mapped over the cartridge.
+    pub fn is_boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
     }
 
-    /// Sets the clocks frequency.
-    pub fn set_frequency(&mut self) {
-        let new_frequency = self.get_frequency();
-        match new_frequency {
-            0 => self.timer_counter = 1024,
-            1 => self.timer_counter = 16,
-            2 => self.timer_counter = 64,
-            3 => self.timer_counter = 256,
-            _ => panic!("Impossible value for frequency: {}", new_frequency)
+    /// Records an error raised while accessing memory,
+    /// keeping only the first until it is taken.
+    pub fn set_fault(&mut self, fault: EmulatorError) {
+        if self.fault.is_none() {
+            self.fault = Some(fault);
         }
     }
 
+    /// Takes the error raised while accessing
+    /// memory, if there is one.
+    pub fn take_fault(&mut self) -> Option<EmulatorError> {
+        self.fault.take()
+    }
+
     /// Performs a direct memory access transfer.
     pub fn dma_transfer(&mut self, byte: u8) {
         let start_address = (byte as u16) << 8;
@@ -118,39 +145,30 @@ impl MemoryManager {
     pub fn write_memory(&mut self, address: u16, byte: u8) {
         match address {
             // Banking
-            0...0x7FFF => self.cartridge.manage_banking(address, byte),
-
-            // Writing to RAM bank
-            0xA000...0xBFFF => {
-                if self.cartridge.ram_write_enabled {
-                    let shifted_address: u16 = address - 0xA000;
-                    let ram_bank = self.cartridge.get_current_ram_bank();
-                    self.cartridge.set_ram(shifted_address + (ram_bank as u16 * 0x2000), byte);
+            0..=0x7FFF => {
+                if let Err(e) = self.cartridge.manage_banking(address, byte) {
+                    self.set_fault(e);
                 }
             },
 
+            // Writing to RAM bank
+            0xA000..=0xBFFF => self.cartridge.write_ram(address, byte),
+
             // Shadow of work RAM
-            0xE000...0xFDFF => {
+            0xE000..=0xFDFF => {
                 self.memory[address as usize] = byte;
                 self.write_memory(address - 0x2000, byte);
             },
 
             // Unusable memory
             // 0xFEA0...0xFEFE => println!("Attempted to write data 0x{:02X} to unusable memory address 0x{:04X}", byte, address),
-            0xFEA0...0xFEFE => {},
-
-            // DIV register
-            0xFF04 => self.memory[0xFF04] = 0,
-
-            // Updating frequency
-            0xFF07 => {
-                let frequency = self.get_frequency();
-                self.memory[TIMER_CONTROLLER as usize] = byte;
-                let new_frequency = self.get_frequency();
-                if frequency != new_frequency {
-                    self.set_frequency();
-                }
-            },
+            0xFEA0..=0xFEFE => {},
+
+            // Serial transfer registers
+            0xFF01 | 0xFF02 => self.serial.write_register(address, byte),
+
+            // Timer registers
+            0xFF04..=0xFF07 => self.timer.write_register(address, byte),
 
             // Reseting scanline
             0xFF44 => self.memory[address as usize] = 0,
@@ -158,6 +176,18 @@ impl MemoryManager {
             // DMA Transfer
             0xFF46 => self.dma_transfer(byte),
 
+            // Unmapping the boot ROM, which
+            // can't be mapped again
+            0xFF50 => {
+                if byte != 0 {
+                    self.boot_rom = None;
+                }
+                self.memory[address as usize] = byte;
+            },
+
+            // Sound registers and wave RAM
+            0xFF10..=0xFF3F => self.apu.write_register(address, byte),
+
             // Write to memory normally in all other cases
             _ => self.memory[address as usize] = byte
         }
@@ -167,69 +197,111 @@ impl MemoryManager {
     pub fn read_memory(&mut self, address: u16) -> u8 {
         
         match address {
+            // Reading ROM bank 0, which MBC1
+            // can remap in mode 1
+            0..=0x3FFF => {
+                if let Some(ref boot_rom) = self.boot_rom {
+                    if address < 0x100 {
+                        return boot_rom[address as usize];
+                    }
+                }
+
+                let rom_bank = self.cartridge.get_lo_rom_bank();
+                let rom_address = address as u32 + (rom_bank as u32 * 0x4000);
+                if rom_address as usize >= self.cartridge.get_rom_size() {
+                    self.set_fault(EmulatorError::RomBankOutOfRange { bank: rom_bank, address });
+                    return 0xFF;
+                }
+                self.cartridge.get_rom(rom_address)
+            },
+
             // Reading ROM bank
-            0x4000...0x7FFF => {
+            0x4000..=0x7FFF => {
                 let shifted_address = address - 0x4000;
                 let rom_bank = self.cartridge.get_current_rom_bank();
-                return self.cartridge.get_rom(shifted_address as u32 + (rom_bank as u32 * 0x4000));
+                let rom_address = shifted_address as u32 + (rom_bank as u32 * 0x4000);
+                if rom_address as usize >= self.cartridge.get_rom_size() {
+                    self.set_fault(EmulatorError::RomBankOutOfRange { bank: rom_bank, address });
+                    return 0xFF;
+                }
+                self.cartridge.get_rom(rom_address)
             },
 
             // Reading RAM bank
-            0xA000...0xBFFF => {
-                let shifted_address = address - 0xA000;
-                let ram_bank = self.cartridge.get_current_ram_bank();
-                return self.cartridge.get_ram(shifted_address + (ram_bank as u16 * 0x2000));
-            },
+            0xA000..=0xBFFF => self.cartridge.read_ram(address),
 
             // Request gamepad's state
-            0xFF00 =>  return self.update_gamepad_state(),
+            0xFF00 => self.update_gamepad_state(),
+
+            // Serial transfer registers
+            0xFF01 | 0xFF02 => self.serial.read_register(address),
+
+            // Timer registers
+            0xFF04..=0xFF07 => self.timer.read_register(address),
+
+            // Sound registers and wave RAM
+            0xFF10..=0xFF3F => self.apu.read_register(address),
 
             // Return byte normally otherwise
-            _ => return self.memory[address as usize]
+            _ => self.memory[address as usize]
         }
     }
 
-    /// Updates the divider register every 256 cycles.
-    pub fn update_div_register(&mut self, cycles: i32) {
-        self.div_counter += cycles;
-        if self.div_counter >= 255 {
-            self.div_counter = 0;
-            self.memory[0xFF04] += 1;
+    /// Updates the timers based on the current
+    /// amount of CPU cycles.
+    pub fn update_timers(&mut self, cycles: i32) {
+        self.cartridge.update_clock(cycles);
+        if self.timer.update(cycles) {
+            self.request_interrupt(2);
         }
     }
 
-    /// Returns whether the clock has been enabled.
-    pub fn clock_enabled(&mut self) -> bool {
-        if (self.read_memory(TIMER_CONTROLLER) & (1 << 2)) >> 2 == 1 {
-            return true;
-        }
-        false
+    /// Resets the divider register.
+    pub fn reset_div(&mut self) {
+        self.timer.reset_div();
     }
 
-    /// Updates the timers based on the current
+    /// Updates the APU based on the current
     /// amount of CPU cycles.
-    pub fn update_timers(&mut self, cycles: i32) {
-        self.update_div_register(cycles);
+    pub fn update_audio(&mut self, cycles: i32) {
+        self.apu.update(cycles);
+    }
 
-        // Update timer only if clock is enabled
-        if self.clock_enabled() {
-            self.timer_counter -= cycles;
+    /// Updates the serial port, requesting the
+    /// serial interrupt when a transfer completes.
+    pub fn update_serial(&mut self, cycles: i32) {
+        if self.serial.update(cycles) {
+            self.request_interrupt(3);
+        }
+    }
 
-            if self.timer_counter <= 0 {
-                self.set_frequency();
+    /// Connects a peer to the link port.
+    pub fn set_link_peer(&mut self, peer: Box<dyn LinkPeer>) {
+        self.serial.set_link_peer(peer);
+    }
 
-                if self.read_memory(TIMER) == 0xFF {
-                    let modulator = self.read_memory(TIMER_MODULATOR);
-                    self.write_memory(TIMER, modulator);
-                    self.request_interrupt(2);
-                }
-                else {
-                    let increment_timer = self.read_memory(TIMER) + 1;
-                    self.write_memory(TIMER, increment_timer);
-                }
-            }
-        }
-        // println!("{}", self.timer_counter);
+    /// Takes the audio samples produced since
+    /// the last call, interleaved left and right.
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.apu.take_samples()
+    }
+
+    /// Writes the cartridge's battery backed
+    /// RAM to its save file if it has changed.
+    pub fn save_ram(&mut self) -> Result<(), EmulatorError> {
+        self.cartridge.save_ram()
+    }
+
+    /// Getter for whether the cartridge's
+    /// rumble motor is on.
+    pub fn is_rumbling(&self) -> bool {
+        self.cartridge.is_rumbling()
+    }
+
+    /// Sets a function to be called whenever the
+    /// cartridge's rumble motor turns on or off.
+    pub fn set_rumble_callback(&mut self, callback: Option<Box<dyn FnMut(bool) + Send>>) {
+        self.cartridge.set_rumble_callback(callback);
     }
 
     /// Requests an interrupt for the given bit,
@@ -0,0 +1,93 @@
+use error::*;
+
+/// Signature at the start of every PNG file.
+static PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Largest amount of data a stored
+/// deflate block can hold.
+static MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// Encodes row-major RGB8 pixels as a PNG. The image data is
+/// left uncompressed, which keeps the encoder small and is
+/// fine for the handful of small images it's used for. PNGs
+/// can't be empty, so both sides must be at least 1 pixel.
+pub fn encode_rgb(width: u32, height: u32, rgb: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    if width == 0 || height == 0 || rgb.len() != width as usize * height as usize * 3 {
+        return Err(EmulatorError::InvalidImageSize { width, height, length: rgb.len() });
+    }
+
+    let mut png = PNG_SIGNATURE.to_vec();
+
+    // 8 bits per channel, RGB, default compression,
+    // filtering and no interlacing
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    // Every scanline starts with its filter type, 0 for none
+    let row_length = width as usize * 3;
+    let mut scanlines = Vec::with_capacity((row_length + 1) * height as usize);
+    for row in rgb.chunks(row_length) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+/// Appends a chunk with its length and CRC.
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps data in a zlib stream made of
+/// uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(if is_final { 0x01 } else { 0x00 });
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// Computes the CRC-32 used by PNG chunks.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if (crc & 1) != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Computes the Adler-32 checksum
+/// ending a zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,309 @@
+use error::*;
+use frame::*;
+use png;
+use serial::*;
+
+use std::fs;
+use std::iter;
+use std::path::PathBuf;
+
+/// Commands sent in printer packets.
+static COMMAND_INIT: u8 = 0x01;
+static COMMAND_PRINT: u8 = 0x02;
+static COMMAND_DATA: u8 = 0x04;
+static COMMAND_STATUS: u8 = 0x0F;
+
+/// Bits of the status byte.
+static STATUS_CHECKSUM_ERROR: u8 = 0x01;
+static STATUS_PRINTING: u8 = 0x02;
+static STATUS_IMAGE_FULL: u8 = 0x04;
+static STATUS_UNPROCESSED: u8 = 0x08;
+
+/// Byte sent back while the game
+/// clocks out the keep alive byte.
+static ALIVE: u8 = 0x81;
+
+/// Size of the printer's image memory.
+static BUFFER_SIZE: usize = 0x2000;
+
+/// Printed images are always 20 tiles wide.
+static PRINT_WIDTH: usize = 160;
+
+/// Number of bytes in one row of 20 tiles.
+static BYTES_PER_TILE_ROW: usize = 20 * 16;
+
+/// Pixel rows of paper fed for each unit of margin.
+static MARGIN_LINE_HEIGHT: usize = 8;
+
+/// Number of status checks a print
+/// takes before the printer is ready.
+static PRINT_STATUS_CHECKS: u8 = 4;
+
+/// Function called with the path of each printed
+/// image, or the error if it couldn't be written.
+pub type PrintCallback = Box<dyn FnMut(Result<PathBuf, EmulatorError>) + Send>;
+
+/// The part of a packet the printer expects next.
+#[derive(Clone, Copy, PartialEq)]
+enum PacketState {
+    MagicLo,
+    MagicHi,
+    Command,
+    Compression,
+    LengthLo,
+    LengthHi,
+    Data,
+    ChecksumLo,
+    ChecksumHi,
+    Alive,
+    Status
+}
+
+/// A Game Boy Printer plugged into the link port. Each
+/// print is written to a numbered PNG in the output
+/// directory.
+pub struct Printer {
+    output_dir: PathBuf,
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+    buffer: Vec<u8>,
+    status: u8,
+    response: u8,
+    remaining_status_checks: u8,
+    print_callback: Option<PrintCallback>
+}
+
+impl Printer {
+
+    /// Creates a printer that writes
+    /// to the given directory.
+    pub fn new(output_dir: PathBuf) -> Printer {
+        Printer {
+            output_dir,
+            state: PacketState::MagicLo,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            status: 0,
+            response: 0,
+            remaining_status_checks: 0,
+            print_callback: None
+        }
+    }
+
+    /// Sets a function to be called with the path of each
+    /// image written, or the error if it couldn't be.
+    pub fn set_print_callback(&mut self, callback: Option<PrintCallback>) {
+        self.print_callback = callback;
+    }
+
+    /// Runs the command of a packet
+    /// whose checksum matched.
+    fn run_command(&mut self) {
+        if self.command == COMMAND_INIT {
+            self.buffer.clear();
+            self.status = 0;
+            self.remaining_status_checks = 0;
+        }
+        else if self.command == COMMAND_DATA {
+            let data = if self.compressed { decompress(&self.data) } else { self.data.clone() };
+            let space = BUFFER_SIZE - self.buffer.len();
+            self.buffer.extend_from_slice(&data[..data.len().min(space)]);
+
+            // An empty data packet marks the end of the image
+            if self.data.is_empty() {
+                self.status |= STATUS_IMAGE_FULL;
+            }
+            else {
+                self.status |= STATUS_UNPROCESSED;
+            }
+        }
+        else if self.command == COMMAND_PRINT && self.data.len() >= 4 {
+            let (sheets, margins, palette) = (self.data[0], self.data[1], self.data[2]);
+
+            // With no image and no margins there's
+            // no paper fed, so nothing is saved
+            if sheets > 0 && (self.buffer.len() >= BYTES_PER_TILE_ROW || margins != 0) {
+                let result = self.print(margins, palette);
+                if let Some(ref mut callback) = self.print_callback {
+                    callback(result);
+                }
+            }
+            self.buffer.clear();
+            self.status = STATUS_PRINTING | STATUS_IMAGE_FULL;
+            self.remaining_status_checks = PRINT_STATUS_CHECKS;
+        }
+        else if self.command == COMMAND_STATUS && self.remaining_status_checks > 0 {
+            self.remaining_status_checks -= 1;
+            if self.remaining_status_checks == 0 {
+                self.status &= !(STATUS_PRINTING | STATUS_IMAGE_FULL);
+            }
+        }
+    }
+
+    /// Renders the image memory with the given palette,
+    /// adding blank paper for the margins, and writes it
+    /// to the next free file in the output directory.
+    fn print(&mut self, margins: u8, palette: u8) -> Result<PathBuf, EmulatorError> {
+
+        // A palette of 0 is treated as the default
+        let palette = if palette == 0 { 0xE4 } else { palette };
+        let tile_rows = self.buffer.len() / BYTES_PER_TILE_ROW;
+        let margin_before = (margins >> 4) as usize * MARGIN_LINE_HEIGHT;
+        let margin_after = (margins & 0x0F) as usize * MARGIN_LINE_HEIGHT;
+        let height = margin_before + tile_rows * 8 + margin_after;
+
+        let mut shades = vec![0u8; PRINT_WIDTH * height];
+        for tile_row in 0..tile_rows {
+            for tile in 0..20 {
+                let tile_start = tile_row * BYTES_PER_TILE_ROW + tile * 16;
+                for line in 0..8 {
+                    let byte_lo = self.buffer[tile_start + line * 2];
+                    let byte_hi = self.buffer[tile_start + line * 2 + 1];
+                    let y = margin_before + tile_row * 8 + line;
+                    for pixel in 0..8 {
+                        let bit = 7 - pixel;
+                        let color_id = (((byte_hi >> bit) & 1) << 1) | ((byte_lo >> bit) & 1);
+                        let shade = (palette >> (color_id * 2)) & 0x03;
+                        shades[y * PRINT_WIDTH + tile * 8 + pixel] = shade;
+                    }
+                }
+            }
+        }
+
+        let mut rgb = Vec::with_capacity(shades.len() * 3);
+        for shade in shades.iter() {
+            rgb.extend_from_slice(&SHADE_RGB[*shade as usize]);
+        }
+
+        fs::create_dir_all(&self.output_dir)?;
+        let mut number = 1;
+        let mut path = self.output_dir.join(format!("print_{:04}.png", number));
+        while path.exists() {
+            number += 1;
+            path = self.output_dir.join(format!("print_{:04}.png", number));
+        }
+        fs::write(&path, png::encode_rgb(PRINT_WIDTH as u32, height as u32, &rgb)?)?;
+        Ok(path)
+    }
+
+    /// Receives the next byte of a packet. The printer
+    /// answers with 0 until the last two bytes, where it
+    /// sends the keep alive byte and then its status.
+    pub fn transfer(&mut self, outgoing: u8) -> u8 {
+        let mut response = 0x00;
+        match self.state {
+            PacketState::MagicLo => {
+                if outgoing == 0x88 {
+                    self.state = PacketState::MagicHi;
+                }
+            },
+            PacketState::MagicHi => {
+                self.state = if outgoing == 0x33 { PacketState::Command } else { PacketState::MagicLo };
+            },
+            PacketState::Command => {
+                self.command = outgoing;
+                self.checksum = outgoing as u16;
+                self.state = PacketState::Compression;
+            },
+            PacketState::Compression => {
+                self.compressed = (outgoing & 0x01) != 0;
+                self.checksum = self.checksum.wrapping_add(outgoing as u16);
+                self.state = PacketState::LengthLo;
+            },
+            PacketState::LengthLo => {
+                self.length = outgoing as u16;
+                self.checksum = self.checksum.wrapping_add(outgoing as u16);
+                self.state = PacketState::LengthHi;
+            },
+            PacketState::LengthHi => {
+                self.length |= (outgoing as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(outgoing as u16);
+                self.data.clear();
+                self.state = if self.length == 0 { PacketState::ChecksumLo } else { PacketState::Data };
+            },
+            PacketState::Data => {
+                self.data.push(outgoing);
+                self.checksum = self.checksum.wrapping_add(outgoing as u16);
+                if self.data.len() == self.length as usize {
+                    self.state = PacketState::ChecksumLo;
+                }
+            },
+            PacketState::ChecksumLo => {
+                self.received_checksum = outgoing as u16;
+                self.state = PacketState::ChecksumHi;
+            },
+            PacketState::ChecksumHi => {
+                self.received_checksum |= (outgoing as u16) << 8;
+                if self.received_checksum == self.checksum {
+                    self.status &= !STATUS_CHECKSUM_ERROR;
+                    self.run_command();
+                }
+                else {
+                    self.status |= STATUS_CHECKSUM_ERROR;
+                }
+                self.state = PacketState::Alive;
+            },
+            PacketState::Alive => {
+                response = ALIVE;
+                self.state = PacketState::Status;
+            },
+            PacketState::Status => {
+                response = self.status;
+                self.state = PacketState::MagicLo;
+            }
+        }
+        response
+    }
+}
+
+impl LinkPeer for Printer {
+    fn start_transfer(&mut self, outgoing: u8) {
+        self.response = self.transfer(outgoing);
+    }
+
+    fn poll_transfer(&mut self) -> Option<u8> {
+        Some(self.response)
+    }
+
+    /// The printer never drives the clock.
+    fn poll_external(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// Expands the run length encoding used by compressed
+/// data packets. A control byte with bit 7 set repeats
+/// the next byte (control & 0x7F) + 2 times, otherwise
+/// the next control + 1 bytes are copied.
+pub fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let control = data[i];
+        i += 1;
+        if (control & 0x80) != 0 {
+            let length = (control & 0x7F) as usize + 2;
+            if let Some(byte) = data.get(i) {
+                output.extend(iter::repeat_n(*byte, length));
+            }
+            i += 1;
+        }
+        else {
+            let length = control as usize + 1;
+            let end = (i + length).min(data.len());
+            output.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+    output
+}
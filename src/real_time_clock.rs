@@ -0,0 +1,257 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of CPU cycles in one second.
+static CYCLES_PER_SECOND: i32 = 4194304;
+
+/// Size of the RTC footer appended to
+/// save files after cartridge RAM.
+pub const RTC_SAVE_SIZE: usize = 48;
+
+/// The clock registers, selected by writing
+/// 0x08 to 0x0C to 0x4000-0x5FFF.
+#[derive(Clone, Copy, Default)]
+struct ClockRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days_lo: u8,
+    days_hi: u8
+}
+
+impl ClockRegisters {
+
+    /// Returns the register selected by
+    /// a value from 0x08 to 0x0C.
+    fn get(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days_lo,
+            0x0C => self.days_hi,
+            _ => 0xFF
+        }
+    }
+
+    /// Returns the 9-bit day counter.
+    fn get_days(&self) -> u16 {
+        ((self.days_hi as u16 & 1) << 8) | self.days_lo as u16
+    }
+
+    /// Sets the 9-bit day counter.
+    fn set_days(&mut self, days: u16) {
+        self.days_lo = (days & 0xFF) as u8;
+        self.days_hi = (self.days_hi & 0xFE) | ((days >> 8) & 1) as u8;
+    }
+
+    /// Advances the clock by one second, carrying into the
+    /// minutes, hours and days. Out of range values written
+    /// by a game count up to their bit limit and wrap to 0
+    /// without carrying, as they do on hardware.
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        let days = self.get_days() + 1;
+        if days > 0x1FF {
+
+            // Set the day counter's carry bit
+            self.days_hi |= 1 << 7;
+        }
+        self.set_days(days & 0x1FF);
+    }
+
+    /// Advances the clock by any number of seconds at
+    /// once, with the same result as ticking that many
+    /// times. Once the day counter passes 511 days,
+    /// only the carry bit records how far it went.
+    fn advance(&mut self, seconds: u64) {
+        let (seconds, minutes) = count_up(self.seconds, seconds, 60, 64);
+        self.seconds = seconds;
+        let (minutes, hours) = count_up(self.minutes, minutes, 60, 64);
+        self.minutes = minutes;
+        let (hours, days) = count_up(self.hours, hours, 24, 32);
+        self.hours = hours;
+
+        let days = self.get_days() as u64 + days;
+        if days > 0x1FF {
+
+            // Set the day counter's carry bit
+            self.days_hi |= 1 << 7;
+        }
+        self.set_days((days & 0x1FF) as u16);
+    }
+}
+
+/// Counts a clock register up by amount, where it carries
+/// at limit and its bits wrap at wrap. Returns the new value
+/// and how many times it carried. Out of range values count
+/// up to wrap and roll over to 0 without carrying first.
+fn count_up(value: u8, amount: u64, limit: u64, wrap: u64) -> (u8, u64) {
+    let mut value = value as u64;
+    let mut amount = amount;
+    if value >= limit {
+        if amount < wrap - value {
+            return ((value + amount) as u8, 0);
+        }
+        amount -= wrap - value;
+        value = 0;
+    }
+
+    let total = value + amount;
+    ((total % limit) as u8, total / limit)
+}
+
+/// The real time clock found on MBC3 cartridges.
+pub struct RealTimeClock {
+    registers: ClockRegisters,
+    latched: ClockRegisters,
+    remaining_cycles: i32
+}
+
+impl RealTimeClock {
+
+    /// Default constructor.
+    pub fn new() -> RealTimeClock {
+        RealTimeClock {
+            registers: ClockRegisters::default(),
+            latched: ClockRegisters::default(),
+            remaining_cycles: CYCLES_PER_SECOND
+        }
+    }
+
+    /// Returns whether the halt bit is set.
+    pub fn is_halted(&self) -> bool {
+        self.registers.days_hi & (1 << 6) != 0
+    }
+
+    /// Copies the running clock into the
+    /// registers the game reads.
+    pub fn latch(&mut self) {
+        self.latched = self.registers;
+    }
+
+    /// Reads a latched clock register.
+    pub fn read_register(&self, register: u8) -> u8 {
+        self.latched.get(register)
+    }
+
+    /// Writes to a clock register.
+    pub fn write_register(&mut self, register: u8, byte: u8) {
+        match register {
+            0x08 => {
+                self.registers.seconds = byte & 0x3F;
+                self.remaining_cycles = CYCLES_PER_SECOND;
+            },
+            0x09 => self.registers.minutes = byte & 0x3F,
+            0x0A => self.registers.hours = byte & 0x1F,
+            0x0B => self.registers.days_lo = byte,
+            0x0C => self.registers.days_hi = byte & 0xC1,
+            _ => {}
+        }
+
+        // Keep writes visible to reads
+        // without waiting for a latch
+        self.latched = self.registers;
+    }
+
+    /// Updates the clock based on the
+    /// current amount of CPU cycles.
+    pub fn update(&mut self, cycles: i32) {
+        if self.is_halted() {
+            return;
+        }
+
+        self.remaining_cycles -= cycles;
+        while self.remaining_cycles <= 0 {
+            self.remaining_cycles += CYCLES_PER_SECOND;
+            self.registers.tick();
+        }
+    }
+
+    /// Advances the clock by the given number of
+    /// seconds, such as the time since a save.
+    pub fn advance(&mut self, seconds: u64) {
+        if self.is_halted() {
+            return;
+        }
+        self.registers.advance(seconds);
+    }
+
+    /// Returns the clock in the footer format used by
+    /// other emulators: the running then latched registers
+    /// as 32-bit values, followed by a 64-bit timestamp.
+    pub fn to_save_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        for registers in [self.registers, self.latched].iter() {
+            for register in 0x08..0x0D {
+                data.extend_from_slice(&(registers.get(register) as u32).to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&unix_time().to_le_bytes());
+        data
+    }
+
+    /// Restores the clock from a save file footer and
+    /// advances it by the time that has passed since.
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE {
+            return;
+        }
+
+        let read_u32 = |index: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&data[index * 4..index * 4 + 4]);
+            u32::from_le_bytes(bytes) as u8
+        };
+        for register in 0x08..0x0D {
+            let index = (register - 0x08) as usize;
+            self.write_register(register, read_u32(index));
+        }
+        self.latched = ClockRegisters {
+            seconds: read_u32(5),
+            minutes: read_u32(6),
+            hours: read_u32(7),
+            days_lo: read_u32(8),
+            days_hi: read_u32(9)
+        };
+
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&data[40..48]);
+        let saved_at = u64::from_le_bytes(timestamp);
+        let now = unix_time();
+        if now > saved_at {
+            self.advance(now - saved_at);
+        }
+    }
+}
+
+impl Default for RealTimeClock {
+    fn default() -> RealTimeClock {
+        RealTimeClock::new()
+    }
+}
+
+/// Returns the current time in seconds
+/// since the Unix epoch.
+fn unix_time() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => 0
+    }
+}
@@ -0,0 +1,56 @@
+/// Converts interleaved left and right samples from
+/// one rate to another using linear interpolation.
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    position: f64,
+    previous: [f32; 2]
+}
+
+impl Resampler {
+
+    /// Creates a resampler from input_rate
+    /// to output_rate samples per second.
+    pub fn new(input_rate: u32, output_rate: u32) -> Resampler {
+        Resampler {
+            input_rate,
+            output_rate,
+            position: 0.0,
+            previous: [0.0; 2]
+        }
+    }
+
+    /// Getter for the output rate.
+    pub fn get_output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Resamples a block of interleaved samples. The last
+    /// sample of each block is kept so the next block
+    /// continues smoothly from it.
+    pub fn resample(&mut self, input: &[f32]) -> Vec<f32> {
+        let frames = input.len() / 2;
+        let step = self.input_rate as f64 / self.output_rate as f64;
+        let mut output = Vec::with_capacity((frames as f64 / step) as usize * 2 + 2);
+
+        // Position 0 is the previous block's last sample
+        // and position n is the input's nth sample
+        while self.position < frames as f64 {
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+
+            for channel in 0..2 {
+                let start = if index == 0 { self.previous[channel] } else { input[(index - 1) * 2 + channel] };
+                let end = input[index * 2 + channel];
+                output.push(start + (end - start) * fraction);
+            }
+            self.position += step;
+        }
+
+        if frames > 0 {
+            self.position -= frames as f64;
+            self.previous = [input[(frames - 1) * 2], input[(frames - 1) * 2 + 1]];
+        }
+        output
+    }
+}
\ No newline at end of file
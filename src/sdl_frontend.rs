@@ -0,0 +1,166 @@
+use apu::*;
+use frame::*;
+use frontend::*;
+use resampler::*;
+
+use sdl2::AudioSubsystem;
+use sdl2::EventPump;
+use sdl2::VideoSubsystem;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+static SCALE: u32 = 2;
+
+/// Most audio, in seconds, allowed to wait in the
+/// queue before new samples are dropped.
+static MAX_QUEUED_SECONDS: f64 = 0.2;
+
+/// Video sink that draws frames to an SDL window.
+pub struct SdlVideoSink {
+    canvas: Canvas<Window>
+}
+
+impl SdlVideoSink {
+
+    /// Opens a window scaled to fit the display.
+    pub fn new(video_subsystem: &VideoSubsystem, title: &str) -> SdlVideoSink {
+        let window = video_subsystem.window(title, SCREEN_WIDTH as u32 * SCALE, SCREEN_HEIGHT as u32 * SCALE)
+            .opengl()
+            .build()
+            .unwrap();
+        let mut canvas = window.into_canvas().build().unwrap();
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        canvas.present();
+
+        SdlVideoSink {
+            canvas
+        }
+    }
+}
+
+impl VideoSink for SdlVideoSink {
+    fn draw_frame(&mut self, frame: &Frame) {
+        for i in 0..SCREEN_WIDTH * SCREEN_HEIGHT {
+            let x = i % SCREEN_WIDTH;
+            let y = i / SCREEN_WIDTH;
+
+            let rgb = frame.get_rgb(x, y);
+            self.canvas.set_draw_color(Color::RGB(rgb[0], rgb[1], rgb[2]));
+            let _ = self.canvas.fill_rect(Rect::new((x * SCALE as usize) as i32, (y * SCALE as usize) as i32, SCALE, SCALE));
+        }
+
+        self.canvas.present();
+    }
+}
+
+/// Input source that reads the keyboard
+/// through an SDL event pump.
+pub struct SdlInputSource {
+    event_pump: EventPump,
+    state: ButtonState
+}
+
+impl SdlInputSource {
+
+    /// Default constructor.
+    pub fn new(event_pump: EventPump) -> SdlInputSource {
+        SdlInputSource {
+            event_pump,
+            state: ButtonState::default()
+        }
+    }
+
+    /// Returns an integer to identify 
+    /// the keycode.
+    pub fn resolve_key(&mut self, key: Keycode) -> Option<i32> {
+        match key {
+            Keycode::D => Some(0),
+            Keycode::A => Some(1),
+            Keycode::W => Some(2),
+            Keycode::S => Some(3),
+            Keycode::K => Some(4),
+            Keycode::J => Some(5),
+            Keycode::Space => Some(6),
+            Keycode::Return => Some(7),
+            _ => None
+        }
+    }
+}
+
+impl InputSource for SdlInputSource {
+    fn poll_buttons(&mut self) -> Option<ButtonState> {
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    return None;
+                },
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    if let Some(key_val) = self.resolve_key(keycode) {
+                        self.state.set_key(key_val, true);
+                    }
+                },
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(key_val) = self.resolve_key(keycode) {
+                        self.state.set_key(key_val, false);
+                    }
+                },
+                _ => {}
+            }
+        }
+        Some(self.state)
+    }
+}
+
+/// Audio sink that resamples the APU's output
+/// and plays it through an SDL audio queue.
+pub struct SdlAudioSink {
+    queue: AudioQueue<f32>,
+    resampler: Resampler
+}
+
+impl SdlAudioSink {
+
+    /// Opens a stereo audio device at the given sample rate,
+    /// usually 44100 or 48000, and starts playback.
+    pub fn new(audio_subsystem: &AudioSubsystem, sample_rate: i32) -> Result<SdlAudioSink, String> {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(sample_rate),
+            channels: Some(2),
+            samples: Some(1024)
+        };
+        let queue: AudioQueue<f32> = audio_subsystem.open_queue(None, &desired_spec)?;
+        let output_rate = queue.spec().freq as u32;
+        queue.resume();
+
+        Ok(SdlAudioSink {
+            queue,
+            resampler: Resampler::new(SAMPLE_RATE, output_rate)
+        })
+    }
+
+    /// Returns how many seconds of audio are
+    /// waiting in the queue to be played.
+    pub fn get_queued_seconds(&self) -> f64 {
+        let bytes_per_second = self.resampler.get_output_rate() as f64 * 2.0 * 4.0;
+        self.queue.size() as f64 / bytes_per_second
+    }
+}
+
+impl AudioSink for SdlAudioSink {
+    fn queue_samples(&mut self, samples: &[f32]) {
+        let resampled = self.resampler.resample(samples);
+
+        // Drop audio rather than let latency build up
+        // when emulation runs ahead of playback
+        if self.get_queued_seconds() < MAX_QUEUED_SECONDS {
+            self.queue.queue(&resampled);
+        }
+    }
+}
\ No newline at end of file
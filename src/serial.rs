@@ -0,0 +1,181 @@
+/// CPU cycles taken to shift one bit
+/// with the 8192 Hz internal clock.
+static CYCLES_PER_BIT: i32 = 512;
+
+/// Something plugged into the other end of the link
+/// cable, such as another Game Boy or a printer.
+pub trait LinkPeer: Send {
+
+    /// Called when the Game Boy starts a transfer with
+    /// its internal clock, sending outgoing to the peer.
+    fn start_transfer(&mut self, outgoing: u8);
+
+    /// Called once the Game Boy has shifted out all 8 bits,
+    /// then once per bit until it returns the byte shifted
+    /// in from the peer. A peer that has to wait for an
+    /// answer returns None meanwhile, so the emulator
+    /// keeps running instead of blocking.
+    fn poll_transfer(&mut self) -> Option<u8>;
+
+    /// Called while the Game Boy waits for the peer to
+    /// drive the external clock. Returns the byte shifted
+    /// in once the peer has clocked a transfer, sending
+    /// outgoing back to it.
+    fn poll_external(&mut self, outgoing: u8) -> Option<u8>;
+
+    /// Called while no transfer is set up. A peer that
+    /// clocks a transfer anyway gets outgoing back straight
+    /// away, and the byte it sent is lost, so it's never
+    /// left waiting for the Game Boy to be ready.
+    fn poll_idle(&mut self, _outgoing: u8) {}
+}
+
+/// Link peer for when no cable is connected. The
+/// input line floats high, so every transfer reads
+/// 0xFF, and the external clock never arrives.
+pub struct NullLinkPeer;
+
+impl LinkPeer for NullLinkPeer {
+    fn start_transfer(&mut self, _outgoing: u8) {}
+
+    fn poll_transfer(&mut self) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    fn poll_external(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// The serial port, made up of the serial transfer
+/// data (SB) and serial control (SC) registers.
+pub struct Serial {
+    data: u8,
+    control: u8,
+    remaining_cycles: i32,
+    peer: Box<dyn LinkPeer>
+}
+
+impl Serial {
+
+    /// Default constructor, with no
+    /// cable connected.
+    pub fn new() -> Serial {
+        Serial {
+            data: 0,
+            control: 0,
+            remaining_cycles: 0,
+            peer: Box::new(NullLinkPeer)
+        }
+    }
+
+    /// Connects a peer to the link port.
+    pub fn set_link_peer(&mut self, peer: Box<dyn LinkPeer>) {
+        self.peer = peer;
+    }
+
+    /// Returns whether a transfer has been
+    /// requested and hasn't finished.
+    fn transfer_active(&self) -> bool {
+        (self.control & 0x80) != 0
+    }
+
+    /// Returns whether the Game Boy
+    /// drives the clock.
+    fn internal_clock(&self) -> bool {
+        (self.control & 0x01) != 0
+    }
+
+    /// Reads SB, or SC with its unused bits set.
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            0xFF01 => self.data,
+            0xFF02 => self.control | 0x7E,
+            _ => 0xFF
+        }
+    }
+
+    /// Writes to SB or SC. Setting bit 7 of SC with the
+    /// internal clock exchanges bytes with the peer, and
+    /// the result appears after all 8 bits are shifted.
+    pub fn write_register(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF01 => self.data = byte,
+            0xFF02 => {
+                self.control = byte & 0x81;
+                if self.transfer_active() && self.internal_clock() {
+                    self.peer.start_transfer(self.data);
+                    self.remaining_cycles = CYCLES_PER_BIT * 8;
+                }
+                else {
+                    self.remaining_cycles = 0;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Updates a transfer in progress based on the current
+    /// amount of CPU cycles. Returns true when a transfer
+    /// completes and the serial interrupt is due.
+    pub fn update(&mut self, cycles: i32) -> bool {
+        if !self.transfer_active() {
+            self.remaining_cycles -= cycles;
+            if self.remaining_cycles <= 0 {
+                self.remaining_cycles += CYCLES_PER_BIT;
+                self.peer.poll_idle(self.data);
+            }
+            return false;
+        }
+
+        if self.internal_clock() {
+            self.remaining_cycles -= cycles;
+            if self.remaining_cycles > 0 {
+                return false;
+            }
+
+            // The transfer stays active until the
+            // peer's byte is in, checking once per bit
+            return match self.peer.poll_transfer() {
+                Some(incoming) => {
+                    self.finish_transfer(incoming);
+                    true
+                },
+                None => {
+                    self.remaining_cycles = CYCLES_PER_BIT;
+                    false
+                }
+            };
+        }
+
+        // Only check for the peer's clock once
+        // per bit so polling stays cheap
+        self.remaining_cycles -= cycles;
+        if self.remaining_cycles > 0 {
+            return false;
+        }
+        self.remaining_cycles += CYCLES_PER_BIT;
+
+        match self.peer.poll_external(self.data) {
+            Some(incoming) => {
+                self.finish_transfer(incoming);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Stores the byte shifted in and
+    /// clears the transfer flag.
+    fn finish_transfer(&mut self, incoming: u8) {
+        self.data = incoming;
+        self.control &= 0x7F;
+        self.remaining_cycles = 0;
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Serial {
+        Serial::new()
+    }
+}
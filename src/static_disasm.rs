@@ -0,0 +1,483 @@
+use cartridge::*;
+use disasm::*;
+use error::*;
+use instructions::*;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Size of a ROM bank.
+static ROM_BANK_SIZE: usize = 0x4000;
+
+/// Where the boot ROM hands over to the cartridge.
+static ENTRY_POINT: u16 = 0x0100;
+
+/// Addresses RST instructions call.
+static RST_VECTORS: [u16; 8] = [0x00, 0x08, 0x10, 0x18, 0x20, 0x28, 0x30, 0x38];
+
+/// Interrupt vectors and the labels given to them.
+static INTERRUPT_VECTORS: [(u16, &str); 5] = [
+    (0x40, "VBlankInterrupt"),
+    (0x48, "StatInterrupt"),
+    (0x50, "TimerInterrupt"),
+    (0x58, "SerialInterrupt"),
+    (0x60, "JoypadInterrupt")
+];
+
+/// The cartridge header after the entry point,
+/// which is always kept as data.
+static HEADER_START: usize = 0x0104;
+static HEADER_END: usize = 0x0150;
+
+/// Number of bytes written on each db line.
+static BYTES_PER_LINE: usize = 16;
+
+/// Shortest run of one byte written with ds
+/// instead of db, such as padding.
+static MIN_FILL_LENGTH: usize = 16;
+
+/// Name of the assembled project's files.
+static PROJECT_NAME: &str = "game";
+
+/// What a byte of the ROM was found to be.
+#[derive(Clone, Copy, PartialEq)]
+enum ByteKind {
+    Data,
+    InstructionStart,
+    InstructionBody
+}
+
+/// Values the walk knows registers hold. A bank switch
+/// is only followed when the value written is known.
+#[derive(Clone, Copy)]
+struct KnownValues {
+    a: Option<u8>,
+    hl: Option<u16>
+}
+
+/// A static disassembly of a ROM. Code is found by walking
+/// from the entry points and following jumps and calls,
+/// replaying writes to the MBC to tell which bank a jump
+/// into 0x4000-0x7FFF lands in. Everything else is data.
+pub struct RomAnalysis {
+    rom: Vec<u8>,
+    kinds: Vec<ByteKind>,
+
+    // ROM offset of the jump or call target
+    // of the instruction at each offset
+    targets: HashMap<usize, usize>,
+
+    // Label of each ROM offset that is jumped to
+    labels: BTreeMap<usize, String>,
+
+    // Offsets jumped to by an earlier walk, where
+    // known values are forgotten
+    jump_targets: HashSet<usize>,
+
+    // MBC2 decodes its registers from
+    // address bit 8 instead of ranges
+    is_mbc2: bool
+}
+
+impl RomAnalysis {
+
+    /// Walks the ROM from the entry point,
+    /// the RST vectors and the interrupt vectors.
+    pub fn analyze(rom: &[u8]) -> Result<RomAnalysis, EmulatorError> {
+
+        // Code can run through a label before the jump to it is
+        // found, so the ROM is walked again with every target
+        // found so far until no new ones turn up. That keeps
+        // what's known at each label independent of the order
+        // the code is walked in.
+        let mut jump_targets = HashSet::new();
+        let mut analysis = RomAnalysis::walk_all(rom, &jump_targets)?;
+        while analysis.labels.keys().any(|target| !jump_targets.contains(target)) {
+            jump_targets.extend(analysis.labels.keys().cloned());
+            analysis = RomAnalysis::walk_all(rom, &jump_targets)?;
+        }
+
+        // Name the entry points that turned out to be code
+        analysis.name_entry(ENTRY_POINT as usize, "Entry".to_string());
+        for vector in RST_VECTORS.iter() {
+            analysis.name_entry(*vector as usize, format!("Rst_{:02X}", vector));
+        }
+        for &(vector, name) in INTERRUPT_VECTORS.iter() {
+            analysis.name_entry(vector as usize, name.to_string());
+        }
+        Ok(analysis)
+    }
+
+    /// Walks the ROM once from every entry point, forgetting
+    /// known values at each of the given jump targets.
+    fn walk_all(rom: &[u8], jump_targets: &HashSet<usize>) -> Result<RomAnalysis, EmulatorError> {
+
+        // Banking is replayed on a cartridge of its own, so
+        // the disassembly sees the same bank numbers as the
+        // emulator. Writes are applied in the order they're
+        // found, which is close enough for the MBCs that
+        // split the bank number over two registers.
+        let mut cartridge = Cartridge::from_bytes(rom)?;
+        let is_mbc2 = matches!(cartridge.get_header().cartridge_type, 5 | 6);
+
+        let mut analysis = RomAnalysis {
+            rom: rom.to_vec(),
+            kinds: vec![ByteKind::Data; rom.len()],
+            targets: HashMap::new(),
+            labels: BTreeMap::new(),
+            jump_targets: jump_targets.clone(),
+            is_mbc2
+        };
+
+        // Bank 1 is mapped at power on. The bank mapped when an
+        // interrupt or RST runs isn't known, unless the ROM
+        // has only the one bank that can be mapped.
+        let bank_count = analysis.get_bank_count();
+        let unknown_bank = if bank_count == 2 { Some(1) } else { None };
+        let mut pending = vec![(ENTRY_POINT as usize, Some(1))];
+        for vector in RST_VECTORS.iter() {
+            pending.push((*vector as usize, unknown_bank));
+        }
+        for &(vector, _) in INTERRUPT_VECTORS.iter() {
+            pending.push((vector as usize, unknown_bank));
+        }
+
+        let mut visited = HashSet::new();
+        while let Some((offset, mapped_bank)) = pending.pop() {
+            analysis.walk(offset, mapped_bank, unknown_bank, &mut cartridge, &mut pending, &mut visited);
+        }
+        Ok(analysis)
+    }
+
+    /// Follows code from offset until it jumps away, returns
+    /// or runs into something that can't be code, queueing
+    /// the targets of its jumps and calls.
+    fn walk(&mut self, start: usize, mut mapped_bank: Option<u16>, unknown_bank: Option<u16>, cartridge: &mut Cartridge,
+            pending: &mut Vec<(usize, Option<u16>)>, visited: &mut HashSet<(usize, Option<u16>)>) {
+        let mut offset = start;
+        let mut known = KnownValues { a: None, hl: None };
+        while offset < self.rom.len() && visited.insert((offset, mapped_bank)) {
+
+            // Other code can jump to a label with
+            // anything in its registers
+            if offset != start && self.jump_targets.contains(&offset) {
+                known = KnownValues { a: None, hl: None };
+            }
+
+            let bank = offset / ROM_BANK_SIZE;
+            let address = get_address(offset);
+            let line = disassemble_rom(&self.rom, bank as u16, address);
+            let length = line.bytes.len();
+
+            // Stop at anything that can't be decoded, runs off the
+            // end of its bank or overlaps code decoded differently
+            if let Instruction::Undefined(_) = line.instruction {
+                break;
+            }
+            if offset % ROM_BANK_SIZE + length > ROM_BANK_SIZE || offset + length > self.rom.len() {
+                break;
+            }
+            if offset < HEADER_END && offset + length > HEADER_START {
+                break;
+            }
+            let overlaps = match self.kinds[offset] {
+                ByteKind::InstructionStart => false,
+                ByteKind::InstructionBody => true,
+                ByteKind::Data => self.kinds[offset + 1..offset + length].iter().any(|kind| *kind != ByteKind::Data)
+            };
+            if overlaps {
+                break;
+            }
+            self.kinds[offset] = ByteKind::InstructionStart;
+            for kind in self.kinds[offset + 1..offset + length].iter_mut() {
+                *kind = ByteKind::InstructionBody;
+            }
+
+            // Code running from 0x4000-0x7FFF is in the mapped bank
+            if bank != 0 {
+                mapped_bank = Some(bank as u16);
+            }
+
+            let bank_count = self.get_bank_count();
+            let next_address = address.wrapping_add(length as u16);
+            let jump_target = match line.instruction {
+                Instruction::Jp { address, .. } | Instruction::Call { address, .. } => Some(address),
+                Instruction::Jr { offset, .. } => Some(next_address.wrapping_add(offset as i16 as u16)),
+                Instruction::Rst(address) => Some(address as u16),
+                _ => None
+            };
+            if let Some(target) = jump_target.and_then(|target| get_offset(target, mapped_bank, bank_count)) {
+                self.targets.insert(offset, target);
+                self.labels.entry(target)
+                    .or_insert_with(|| format!("Label_{:03X}_{:04X}", target / ROM_BANK_SIZE, get_address(target)));
+                pending.push((target, mapped_bank.or(unknown_bank)));
+            }
+
+            // Replay writes to the MBC, forgetting the
+            // bank if the value written isn't known
+            let write = match line.instruction {
+                Instruction::LdAddressA(address) => Some((address, known.a)),
+                Instruction::Ld8 { dest: Reg8::IndirectHL, src: Reg8::A } => known.hl.map(|address| (address, known.a)),
+                Instruction::Ld8Immediate { dest: Reg8::IndirectHL, value } => known.hl.map(|address| (address, Some(value))),
+                _ => None
+            };
+            if let Some((address, value)) = write {
+                if address < 0x8000 {
+                    let previous_bank = mapped_bank;
+                    match value {
+                        Some(value) => {
+
+                            // Writes that leave the replayed bank alone,
+                            // such as enabling RAM, keep what's known
+                            let replayed_bank = cartridge.get_current_rom_bank();
+                            let _ = cartridge.manage_banking(address, value);
+                            let new_bank = cartridge.get_current_rom_bank();
+                            if new_bank != replayed_bank || self.selects_rom_bank(address) {
+                                mapped_bank = Some(new_bank % bank_count as u16);
+                            }
+                        },
+                        None if self.selects_rom_bank(address) => mapped_bank = unknown_bank,
+                        None => {}
+                    }
+
+                    // Code that switches its own bank out
+                    // carries on somewhere unknown
+                    if bank != 0 && mapped_bank != previous_bank {
+                        break;
+                    }
+                }
+            }
+
+            known = update_known_values(known, &line.instruction);
+            match line.instruction {
+                Instruction::Jp { condition: None, .. } | Instruction::Jr { condition: None, .. } | Instruction::JpHl |
+                Instruction::Ret { condition: None } | Instruction::Reti => break,
+                _ => {}
+            }
+
+            // Code in bank 0 can't run on into the switchable bank
+            // in a way that can be followed, nor past the last bank
+            offset += length;
+            if offset.is_multiple_of(ROM_BANK_SIZE) {
+                break;
+            }
+        }
+    }
+
+    /// Returns whether a write to address selects the ROM
+    /// bank. MBC2 only switches banks when bit 8 of the
+    /// address is set, anywhere in 0x0000-0x3FFF.
+    fn selects_rom_bank(&self, address: u16) -> bool {
+        if self.is_mbc2 {
+            address < 0x4000 && (address & 0x0100) != 0
+        }
+        else {
+            (0x2000..0x4000).contains(&address)
+        }
+    }
+
+    /// Gives an entry point its own label, if it's code.
+    fn name_entry(&mut self, offset: usize, name: String) {
+        if self.is_code(offset) {
+            self.labels.insert(offset, name);
+        }
+    }
+
+    /// Getter for the number of banks in the ROM.
+    pub fn get_bank_count(&self) -> usize {
+        self.rom.len() / ROM_BANK_SIZE
+    }
+
+    /// Returns whether an instruction
+    /// starts at the given ROM offset.
+    pub fn is_code(&self, offset: usize) -> bool {
+        self.kinds.get(offset) == Some(&ByteKind::InstructionStart)
+    }
+
+    /// Returns the number of bytes found to be code.
+    pub fn get_code_size(&self) -> usize {
+        self.kinds.iter().filter(|kind| **kind != ByteKind::Data).count()
+    }
+
+    /// Writes the source of one bank as an RGBDS section.
+    pub fn write_bank(&self, bank: usize) -> String {
+        let mut source = if bank == 0 {
+            String::from("SECTION \"ROM Bank $000\", ROM0[$0000]\n")
+        }
+        else {
+            format!("SECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:03X}]\n", bank, bank)
+        };
+
+        let end = (bank + 1) * ROM_BANK_SIZE;
+        let mut offset = bank * ROM_BANK_SIZE;
+        while offset < end {
+            if let Some(label) = self.labels.get(&offset) {
+                source.push_str(&format!("\n{}::\n", label));
+            }
+
+            if self.is_code(offset) {
+                let line = disassemble_rom(&self.rom, bank as u16, get_address(offset));
+                source.push_str(&format!("    {}\n", self.format_code(offset, &line)));
+                offset += line.bytes.len();
+            }
+            else {
+                let mut data_end = offset + 1;
+                while data_end < end && !self.is_code(data_end) && !self.labels.contains_key(&data_end) {
+                    data_end += 1;
+                }
+                write_data(&mut source, &self.rom[offset..data_end]);
+                offset = data_end;
+            }
+        }
+        source
+    }
+
+    /// Formats an instruction found to be code, with its
+    /// target written as a label where there is one.
+    fn format_code(&self, offset: usize, line: &DisassembledInstruction) -> String {
+
+        // RGBDS always pads STOP with a 0, so any other
+        // padding byte has to be written out as data
+        if line.instruction == Instruction::Stop && line.bytes[1] != 0 {
+            return format!("db ${:02X}, ${:02X}", line.bytes[0], line.bytes[1]);
+        }
+
+        // Some versions of rgbasm shorten ld to and from
+        // 0xFF00-0xFFFF into ldh, and the same versions put
+        // a nop after halt. rgbasm also rejects relative
+        // jumps that wrap around the address space. All of
+        // those are kept as data.
+        let keep_as_data = match line.instruction {
+            Instruction::LdAddressA(address) | Instruction::LdAAddress(address) => address >= 0xFF00,
+            Instruction::Halt => true,
+            Instruction::Jr { offset, .. } => !(0..=0xFFFF).contains(&(line.get_next_address() as i32 + offset as i32)),
+            _ => false
+        };
+        if keep_as_data {
+            let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("${:02X}", byte)).collect();
+            return format!("db {} ; {}", bytes.join(", "), line.text);
+        }
+
+        let target_name = self.targets.get(&offset)
+            .filter(|target| self.is_code(**target))
+            .and_then(|target| self.labels.get(target));
+        format_instruction_with_target(&line.instruction, line.get_next_address(), target_name.map(|name| name.as_str()))
+    }
+
+    /// Writes an RGBDS project to output_dir, with a source
+    /// file for each bank, a main file including them and a
+    /// Makefile that assembles them back into the ROM.
+    pub fn write_project(&self, output_dir: &Path) -> Result<(), EmulatorError> {
+        fs::create_dir_all(output_dir)?;
+
+        let mut main_source = String::new();
+        for bank in 0..self.get_bank_count() {
+            let file_name = format!("bank_{:03X}.asm", bank);
+            fs::write(output_dir.join(&file_name), self.write_bank(bank))?;
+            main_source.push_str(&format!("INCLUDE \"{}\"\n", file_name));
+        }
+        fs::write(output_dir.join(format!("{}.asm", PROJECT_NAME)), main_source)?;
+
+        // Flags are given explicitly so the ROM doesn't
+        // depend on the installed RGBDS's defaults
+        let makefile = format!("ASFLAGS = -p 0xFF\nLDFLAGS = -p 0xFF\n\n\
+                                {name}.gb: {name}.o\n\trgblink $(LDFLAGS) -o {name}.gb {name}.o\n\n\
+                                {name}.o: {name}.asm bank_*.asm\n\trgbasm $(ASFLAGS) -o {name}.o {name}.asm\n", name = PROJECT_NAME);
+        fs::write(output_dir.join("Makefile"), makefile)?;
+        Ok(())
+    }
+}
+
+/// Returns the address a ROM offset is seen at
+/// while its bank is mapped.
+fn get_address(offset: usize) -> u16 {
+    if offset < ROM_BANK_SIZE {
+        offset as u16
+    }
+    else {
+        (ROM_BANK_SIZE + offset % ROM_BANK_SIZE) as u16
+    }
+}
+
+/// Returns the ROM offset an address reads from with
+/// mapped_bank in 0x4000-0x7FFF, if it is in ROM and
+/// the bank is known.
+fn get_offset(address: u16, mapped_bank: Option<u16>, bank_count: usize) -> Option<usize> {
+    match address {
+        0..=0x3FFF => Some(address as usize),
+        0x4000..=0x7FFF => mapped_bank.map(|bank| (bank as usize % bank_count) * ROM_BANK_SIZE + (address as usize - ROM_BANK_SIZE)),
+        _ => None
+    }
+}
+
+/// Returns what's known about A and HL after an
+/// instruction runs. Calls and RSTs forget both,
+/// since the routine can change them.
+fn update_known_values(known: KnownValues, instruction: &Instruction) -> KnownValues {
+    let mut known = known;
+    match *instruction {
+        Instruction::Ld8Immediate { dest: Reg8::A, value } => known.a = Some(value),
+        Instruction::Alu { op: AluOp::Xor, src: Reg8::A } => known.a = Some(0),
+        Instruction::Ld16Immediate { dest: Reg16::HL, value } => known.hl = Some(value),
+        Instruction::Inc16(Reg16::HL) => known.hl = known.hl.map(|hl| hl.wrapping_add(1)),
+        Instruction::Dec16(Reg16::HL) => known.hl = known.hl.map(|hl| hl.wrapping_sub(1)),
+        Instruction::LdIndirectA(Indirect::HLIncrement) => known.hl = known.hl.map(|hl| hl.wrapping_add(1)),
+        Instruction::LdIndirectA(Indirect::HLDecrement) => known.hl = known.hl.map(|hl| hl.wrapping_sub(1)),
+        Instruction::LdAIndirect(Indirect::HLIncrement) => {
+            known.a = None;
+            known.hl = known.hl.map(|hl| hl.wrapping_add(1));
+        },
+        Instruction::LdAIndirect(Indirect::HLDecrement) => {
+            known.a = None;
+            known.hl = known.hl.map(|hl| hl.wrapping_sub(1));
+        },
+        Instruction::Call { .. } | Instruction::Rst(_) => known = KnownValues { a: None, hl: None },
+
+        // CP only compares, leaving A as it was
+        Instruction::Alu { op: AluOp::Cp, .. } | Instruction::AluImmediate { op: AluOp::Cp, .. } => {},
+        Instruction::Ld8 { dest, .. } | Instruction::Ld8Immediate { dest, .. } | Instruction::Inc8(dest) |
+        Instruction::Dec8(dest) | Instruction::Shift { target: dest, .. } | Instruction::Res { target: dest, .. } |
+        Instruction::Set { target: dest, .. } => {
+            match dest {
+                Reg8::A => known.a = None,
+                Reg8::H | Reg8::L => known.hl = None,
+                _ => {}
+            }
+        },
+        Instruction::Ld16Immediate { .. } | Instruction::Inc16(_) | Instruction::Dec16(_) | Instruction::Push(_) => {},
+        Instruction::AddHl(_) | Instruction::LdHlSpOffset(_) | Instruction::Pop(Reg16Stack::HL) => known.hl = None,
+        Instruction::LdAIndirect(_) | Instruction::LdAAddress(_) | Instruction::LdhAAddress(_) | Instruction::LdhAC |
+        Instruction::Pop(Reg16Stack::AF) | Instruction::Alu { .. } | Instruction::AluImmediate { .. } | Instruction::Daa |
+        Instruction::Cpl | Instruction::Rlca | Instruction::Rrca | Instruction::Rla | Instruction::Rra => known.a = None,
+        _ => {}
+    }
+    known
+}
+
+/// Writes bytes that aren't code, using ds for
+/// long runs of one value and db for the rest.
+fn write_data(source: &mut String, data: &[u8]) {
+    let mut start = 0;
+    while start < data.len() {
+        let run = data[start..].iter().take_while(|byte| **byte == data[start]).count();
+        if run >= MIN_FILL_LENGTH {
+            source.push_str(&format!("    ds {}, ${:02X}\n", run, data[start]));
+            start += run;
+            continue;
+        }
+
+        // Stop a db line where the next long run starts
+        let mut end = start;
+        while end < data.len() && end - start < BYTES_PER_LINE {
+            let next_run = data[end..].iter().take_while(|byte| **byte == data[end]).count();
+            if next_run >= MIN_FILL_LENGTH {
+                break;
+            }
+            end += 1;
+        }
+        let bytes: Vec<String> = data[start..end].iter().map(|byte| format!("${:02X}", byte)).collect();
+        source.push_str(&format!("    db {}\n", bytes.join(", ")));
+        start = end;
+    }
+}
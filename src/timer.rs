@@ -0,0 +1,163 @@
+/// Number of CPU cycles in one machine cycle,
+/// the smallest step the timer advances by.
+static CYCLES_PER_STEP: i32 = 4;
+
+/// Bit of the system counter whose falling edge
+/// increments TIMA, for each TAC clock select.
+static TAC_COUNTER_BITS: [u16; 4] = [9, 3, 5, 7];
+
+/// Progress of TIMA being reloaded after it overflows.
+#[derive(Clone, Copy, PartialEq)]
+enum ReloadState {
+    Idle,
+
+    // TIMA overflowed and reads 0 for one machine cycle.
+    // Writing TIMA now cancels the reload and interrupt.
+    Pending,
+
+    // TMA was just copied into TIMA. Writes to TIMA are
+    // ignored and writes to TMA also go to TIMA.
+    Reloading
+}
+
+/// The timer, built around the 16-bit system counter
+/// whose upper byte is DIV. TIMA counts falling edges
+/// of one of the counter's bits while enabled.
+pub struct Timer {
+    system_counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    reload_state: ReloadState,
+    remaining_cycles: i32
+}
+
+impl Timer {
+
+    /// Default constructor, for the
+    /// state at power on.
+    pub fn new() -> Timer {
+        Timer {
+            system_counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            reload_state: ReloadState::Idle,
+            remaining_cycles: 0
+        }
+    }
+
+    /// Setter for the system counter, such as
+    /// the value the boot ROM leaves behind.
+    pub fn set_system_counter(&mut self, system_counter: u16) {
+        self.system_counter = system_counter;
+    }
+
+    /// Returns the signal TIMA counts falling edges of,
+    /// the selected counter bit ANDed with the enable bit.
+    fn timer_signal(&self) -> bool {
+        let bit = TAC_COUNTER_BITS[(self.tac & 0x03) as usize];
+        (self.tac & 0x04) != 0 && (self.system_counter & (1 << bit)) != 0
+    }
+
+    /// Increments TIMA, starting the
+    /// reload delay if it overflows.
+    fn increment_tima(&mut self) {
+        let (tima, overflowed) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        if overflowed {
+            self.reload_state = ReloadState::Pending;
+        }
+    }
+
+    /// Changes the system counter, incrementing TIMA
+    /// if the change gives a falling edge.
+    fn set_counter(&mut self, system_counter: u16) {
+        let signal = self.timer_signal();
+        self.system_counter = system_counter;
+        if signal && !self.timer_signal() {
+            self.increment_tima();
+        }
+    }
+
+    /// Reads DIV, TIMA, TMA or TAC.
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            0xFF04 => (self.system_counter >> 8) as u8,
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            0xFF07 => self.tac | 0xF8,
+            _ => 0xFF
+        }
+    }
+
+    /// Writes to DIV, TIMA, TMA or TAC. Resetting the counter
+    /// or changing TAC can drop the timer signal, which
+    /// increments TIMA just like a normal falling edge.
+    pub fn write_register(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF04 => self.set_counter(0),
+            0xFF05 => {
+                match self.reload_state {
+                    ReloadState::Pending => {
+                        self.tima = byte;
+                        self.reload_state = ReloadState::Idle;
+                    },
+                    ReloadState::Reloading => {},
+                    ReloadState::Idle => self.tima = byte
+                }
+            },
+            0xFF06 => {
+                self.tma = byte;
+                if self.reload_state == ReloadState::Reloading {
+                    self.tima = byte;
+                }
+            },
+            0xFF07 => {
+                let signal = self.timer_signal();
+                self.tac = byte & 0x07;
+                if signal && !self.timer_signal() {
+                    self.increment_tima();
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Resets DIV, as STOP does.
+    pub fn reset_div(&mut self) {
+        self.set_counter(0);
+    }
+
+    /// Updates the timer based on the current amount of
+    /// CPU cycles, one machine cycle at a time. Returns
+    /// true when TIMA was reloaded and the timer
+    /// interrupt is due.
+    pub fn update(&mut self, cycles: i32) -> bool {
+        let mut interrupt = false;
+        self.remaining_cycles += cycles;
+        while self.remaining_cycles >= CYCLES_PER_STEP {
+            self.remaining_cycles -= CYCLES_PER_STEP;
+
+            match self.reload_state {
+                ReloadState::Pending => {
+                    self.tima = self.tma;
+                    self.reload_state = ReloadState::Reloading;
+                    interrupt = true;
+                },
+                ReloadState::Reloading => self.reload_state = ReloadState::Idle,
+                ReloadState::Idle => {}
+            }
+
+            let system_counter = self.system_counter.wrapping_add(CYCLES_PER_STEP as u16);
+            self.set_counter(system_counter);
+        }
+        interrupt
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Timer {
+        Timer::new()
+    }
+}
@@ -0,0 +1,74 @@
+extern crate rusty_boy_dmg;
+
+mod common;
+
+use common::*;
+use rusty_boy_dmg::error::*;
+
+/// MBC5 with RAM and a battery.
+static MBC5: u8 = 0x1B;
+
+#[test]
+fn mbc5_bank_wraps_to_rom_size() {
+    let mut gameboy = boot_banked(MBC5, 4, 4);
+    gameboy.memory_manager.write_memory(0x2000, 0x10);
+    gameboy.memory_manager.write_memory(0x3000, 0x01);
+
+    assert_eq!(gameboy.memory_manager.read_memory(0x4000), 0);
+    assert!(gameboy.memory_manager.take_fault().is_none());
+
+    gameboy.memory_manager.write_memory(0x2000, 0x07);
+    assert_eq!(gameboy.memory_manager.read_memory(0x4000), 3);
+}
+
+#[test]
+fn mbc5_bank_past_short_image_faults() {
+    let mut gameboy = boot_banked(MBC5, 4, 8);
+    gameboy.memory_manager.write_memory(0x2000, 0x05);
+
+    assert_eq!(gameboy.memory_manager.read_memory(0x4000), 0xFF);
+    match gameboy.memory_manager.take_fault() {
+        Some(EmulatorError::RomBankOutOfRange { bank, .. }) => assert_eq!(bank, 5),
+        _ => panic!("expected RomBankOutOfRange")
+    }
+}
+
+/// MBC2 with a battery.
+static MBC2: u8 = 0x06;
+
+#[test]
+fn mbc2_bank_wraps_to_rom_size() {
+    let mut gameboy = boot_banked(MBC2, 8, 8);
+    gameboy.memory_manager.write_memory(0x2100, 0x0F);
+
+    assert_eq!(gameboy.memory_manager.read_memory(0x4000), 7);
+    assert!(gameboy.memory_manager.take_fault().is_none());
+}
+
+/// MBC3 with a clock, RAM and a battery.
+static MBC3: u8 = 0x10;
+
+#[test]
+fn mbc3_bank_wraps_to_rom_size() {
+    let mut gameboy = boot_banked(MBC3, 16, 16);
+    gameboy.memory_manager.write_memory(0x2000, 0x7E);
+
+    assert_eq!(gameboy.memory_manager.read_memory(0x4000), 14);
+    assert!(gameboy.memory_manager.take_fault().is_none());
+}
+
+/// MBC1 with no RAM.
+static MBC1: u8 = 0x01;
+
+#[test]
+fn mbc1_mode_1_bank_past_short_image_faults() {
+    let mut gameboy = boot_banked(MBC1, 32, 64);
+    gameboy.memory_manager.write_memory(0x6000, 0x01);
+    gameboy.memory_manager.write_memory(0x4000, 0x01);
+
+    assert_eq!(gameboy.memory_manager.read_memory(0x0000), 0xFF);
+    match gameboy.memory_manager.take_fault() {
+        Some(EmulatorError::RomBankOutOfRange { bank, .. }) => assert_eq!(bank, 0x20),
+        _ => panic!("expected RomBankOutOfRange")
+    }
+}
@@ -0,0 +1,69 @@
+#![allow(dead_code)]
+
+use rusty_boy_dmg::cartridge::*;
+use rusty_boy_dmg::gameboy::*;
+
+/// Builds a 32 KiB ROM with no MBC, filled with
+/// NOPs and with each piece of code at its address.
+pub fn build_rom(code: &[(u16, &[u8])]) -> Vec<u8> {
+    let mut rom = vec![0x00; 0x8000];
+    for &(address, bytes) in code {
+        let start = address as usize;
+        rom[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+    rom
+}
+
+/// Creates a Gameboy in its post-boot state
+/// running a ROM built from the given code.
+pub fn boot(code: &[(u16, &[u8])]) -> Gameboy {
+    Gameboy::new(Cartridge::from_bytes(&build_rom(code)).unwrap())
+}
+
+/// Steps instructions until PC reaches address,
+/// failing if it takes more than limit steps.
+pub fn run_to(gameboy: &mut Gameboy, address: u16, limit: usize) {
+    for _ in 0..limit {
+        if gameboy.cpu.get_reg_pc() == address {
+            return;
+        }
+        gameboy.step_instruction().unwrap();
+    }
+    panic!("PC never reached {:04X}, stopped at {:04X}", address, gameboy.cpu.get_reg_pc());
+}
+
+/// Builds a ROM of bank_count banks for a cartridge type,
+/// with the header declaring declared_bank_count banks and
+/// each bank's first byte holding its own bank number.
+pub fn build_banked_rom(cartridge_type: u8, bank_count: usize, declared_bank_count: usize) -> Vec<u8> {
+    let mut rom = vec![0x00; bank_count * 0x4000];
+    for bank in 0..bank_count {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    rom[0x147] = cartridge_type;
+    rom[0x148] = (declared_bank_count / 2).trailing_zeros() as u8;
+    rom
+}
+
+/// Creates a Gameboy running a banked ROM
+/// built by build_banked_rom.
+pub fn boot_banked(cartridge_type: u8, bank_count: usize, declared_bank_count: usize) -> Gameboy {
+    let rom = build_banked_rom(cartridge_type, bank_count, declared_bank_count);
+    Gameboy::new(Cartridge::from_bytes(&rom).unwrap())
+}
+
+/// Code that gives tile 0, which fills the background,
+/// a top row of four black then four white pixels and
+/// then loops forever at 0x0108.
+pub static STRIPES: [u8; 10] = [
+    0x3E, 0xF0,          // ld a, $F0
+    0xEA, 0x00, 0x80,    // ld [$8000], a
+    0xEA, 0x01, 0x80,    // ld [$8001], a
+    0x18, 0xFE           // jr @
+];
+
+/// Returns the shade the stripes
+/// code draws at x, y.
+pub fn stripe_shade(x: usize, y: usize) -> u8 {
+    if y.is_multiple_of(8) && x % 8 < 4 { 3 } else { 0 }
+}
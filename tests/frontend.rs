@@ -0,0 +1,68 @@
+extern crate rusty_boy_dmg;
+
+mod common;
+
+use common::*;
+use rusty_boy_dmg::frame::*;
+use rusty_boy_dmg::frontend::*;
+
+/// Draws the stripes, then selects the action
+/// buttons and copies P1 to 0xFF80 forever.
+static READ_BUTTONS: [u8; 8] = [
+    0x3E, 0x10,    // ld a, $10
+    0xE0, 0x00,    // ldh [$FF00], a
+    0xF0, 0x00,    // ldh a, [$FF00]
+    0xE0, 0x80     // ldh [$FF80], a
+];
+
+/// Builds the code for the button reading ROM.
+fn read_buttons_code() -> Vec<u8> {
+    let mut code = STRIPES[..8].to_vec();
+    code.extend_from_slice(&READ_BUTTONS);
+    code.extend_from_slice(&[0x18, 0xFA]);    // jr back to the ldh a, [$FF00]
+    code
+}
+
+#[test]
+fn step_records_frames_until_input_closes() {
+    let code = read_buttons_code();
+    let mut gameboy = boot(&[(0x100, &code)]);
+
+    let mut video = FrameRecorder::new();
+    let mut audio = SampleRecorder::new();
+    let mut input = ScriptedInputSource::new(vec![ButtonState::default(); 3]);
+    while gameboy.step(&mut video, &mut audio, &mut input).unwrap() {}
+
+    assert_eq!(video.frames_drawn, 3);
+    assert_eq!(video.last_frame.get_number(), gameboy.get_frame_count());
+    assert!(!audio.samples.is_empty());
+
+    let rgba = video.last_frame.to_rgba();
+    assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let index = (y * SCREEN_WIDTH + x) * 4;
+            let expected = SHADE_RGB[stripe_shade(x, y) as usize];
+            assert_eq!(&rgba[index..index + 3], &expected[..], "pixel {}, {}", x, y);
+            assert_eq!(rgba[index + 3], 0xFF);
+        }
+    }
+}
+
+#[test]
+fn scripted_buttons_reach_the_joypad_register() {
+    let code = read_buttons_code();
+    let mut gameboy = boot(&[(0x100, &code)]);
+
+    let pressed = ButtonState { a: true, start: true, ..ButtonState::default() };
+    let mut input = ScriptedInputSource::new(vec![ButtonState::default(), pressed]);
+    let mut video = NullVideoSink;
+    let mut audio = NullAudioSink;
+
+    // Buttons read low when pressed
+    gameboy.step(&mut video, &mut audio, &mut input).unwrap();
+    assert_eq!(gameboy.memory_manager.read_memory(0xFF80) & 0x0F, 0x0F);
+    gameboy.step(&mut video, &mut audio, &mut input).unwrap();
+    assert_eq!(gameboy.memory_manager.read_memory(0xFF80) & 0x0F, 0x06);
+    assert!(!gameboy.step(&mut video, &mut audio, &mut input).unwrap());
+}
@@ -0,0 +1,51 @@
+extern crate rusty_boy_dmg;
+
+mod common;
+
+use common::*;
+
+/// Enables the V-Blank interrupt and requests it,
+/// so it's pending before HALT runs.
+static REQUEST_VBLANK: [u8; 6] = [
+    0x3E, 0x01,    // ld a, $01
+    0xE0, 0xFF,    // ldh [$FFFF], a
+    0xE0, 0x0F     // ldh [$FF0F], a
+];
+
+#[test]
+fn halt_bug_runs_next_byte_twice_with_ime_off() {
+    let mut code = REQUEST_VBLANK.to_vec();
+    code.extend_from_slice(&[
+        0xAF,          // xor a
+        0x76,          // halt
+        0x3C,          // inc a
+        0xE0, 0x80     // ldh [$FF80], a
+    ]);
+    let mut gameboy = boot(&[(0x100, &code)]);
+    run_to(&mut gameboy, 0x10B, 10);
+
+    assert_eq!(gameboy.memory_manager.read_memory(0xFF80), 2);
+}
+
+#[test]
+fn ei_then_halt_returns_to_halt_after_interrupt() {
+    let mut code = REQUEST_VBLANK.to_vec();
+    code.extend_from_slice(&[
+        0xFB,          // ei
+        0x76           // halt
+    ]);
+    let handler = [0xC3, 0x00, 0x02];    // jp $0200
+    let mut gameboy = boot(&[(0x100, &code), (0x40, &handler)]);
+    run_to(&mut gameboy, 0x107, 10);
+
+    // The interrupt is dispatched as HALT finishes,
+    // pushing the address of the HALT itself
+    gameboy.step_instruction().unwrap();
+    assert_eq!(gameboy.cpu.get_reg_pc(), 0x40);
+    assert_eq!(gameboy.memory_manager.read_memory(0xFFFC), 0x07);
+    assert_eq!(gameboy.memory_manager.read_memory(0xFFFD), 0x01);
+
+    // The handler runs from its first byte
+    gameboy.step_instruction().unwrap();
+    assert_eq!(gameboy.cpu.get_reg_pc(), 0x200);
+}
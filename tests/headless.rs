@@ -0,0 +1,32 @@
+extern crate rusty_boy_dmg;
+
+mod common;
+
+use common::*;
+use rusty_boy_dmg::frame::*;
+
+/// Runs the stripes ROM for a number of frames
+/// and returns the shades of the last one.
+fn run_stripes(frames: usize) -> Vec<u8> {
+    let mut gameboy = boot(&[(0x100, &STRIPES)]);
+    for _ in 0..frames {
+        gameboy.run_frame().unwrap();
+    }
+    assert_eq!(gameboy.get_frame_count(), frames as u64);
+    gameboy.get_frame().get_shades().to_vec()
+}
+
+#[test]
+fn runs_without_a_display() {
+    let shades = run_stripes(60);
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            assert_eq!(shades[y * SCREEN_WIDTH + x], stripe_shade(x, y), "pixel {}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn runs_are_deterministic() {
+    assert!(run_stripes(10) == run_stripes(10));
+}
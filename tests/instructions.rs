@@ -0,0 +1,110 @@
+extern crate rusty_boy_dmg;
+
+use rusty_boy_dmg::instructions::*;
+
+/// Length in bytes of each unprefixed opcode, from the Pan
+/// Docs opcode table. 0xCB is checked with the CB opcodes,
+/// and undefined opcodes take up a single byte.
+static LENGTHS: [usize; 256] = [
+    1, 3, 1, 1, 1, 1, 2, 1, 3, 1, 1, 1, 1, 1, 2, 1,
+    2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1,
+    2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1,
+    2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 3, 3, 3, 1, 2, 1, 1, 1, 3, 2, 3, 3, 2, 1,
+    1, 1, 3, 1, 3, 1, 2, 1, 1, 1, 3, 1, 3, 1, 2, 1,
+    2, 1, 1, 1, 1, 1, 2, 1, 2, 1, 3, 1, 1, 1, 2, 1,
+    2, 1, 1, 1, 1, 1, 2, 1, 2, 1, 3, 1, 1, 1, 2, 1
+];
+
+/// Cycles taken by each unprefixed opcode, with conditional
+/// ones counted as branching. Undefined opcodes take 4.
+static CYCLES: [i32; 256] = [
+     4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4,
+     4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4,
+    12, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4,
+    12, 12,  8,  8, 12, 12, 12,  4, 12,  8,  8,  8,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4,
+    20, 12, 16, 16, 24, 16,  8, 16, 20, 16, 16,  0, 24, 24,  8, 16,
+    20, 12, 16,  4, 24, 16,  8, 16, 20, 16, 16,  4, 24,  4,  8, 16,
+    12, 12,  8,  4,  4, 16,  8, 16, 16,  4, 16,  4,  4,  4,  8, 16,
+    12, 12,  8,  4,  4, 16,  8, 16, 12,  8, 16,  4,  4,  4,  8, 16
+];
+
+/// Cycles taken by conditional opcodes
+/// when they don't branch.
+static NOT_TAKEN_CYCLES: [(usize, i32); 16] = [
+    (0x20, 8), (0x28, 8), (0x30, 8), (0x38, 8),
+    (0xC0, 8), (0xC8, 8), (0xD0, 8), (0xD8, 8),
+    (0xC2, 12), (0xCA, 12), (0xD2, 12), (0xDA, 12),
+    (0xC4, 12), (0xCC, 12), (0xD4, 12), (0xDC, 12)
+];
+
+/// Decodes bytes, returning the instruction
+/// and how many bytes it took up.
+fn decode_bytes(bytes: &[u8]) -> (Instruction, usize) {
+    let mut length = 0;
+    let instruction = decode(|| {
+        length += 1;
+        bytes[length - 1]
+    });
+    (instruction, length)
+}
+
+#[test]
+fn opcodes_match_reference_lengths_and_cycles() {
+    for opcode in (0..256).filter(|&opcode| opcode != 0xCB) {
+        let (instruction, length) = decode_bytes(&[opcode as u8, 0x00, 0x00]);
+        let not_taken = NOT_TAKEN_CYCLES.iter()
+            .find(|&&(conditional, _)| conditional == opcode)
+            .map(|&(_, cycles)| cycles)
+            .unwrap_or(CYCLES[opcode]);
+
+        assert_eq!(length, LENGTHS[opcode], "length of {:02X}", opcode);
+        assert_eq!(instruction.get_cycles(true), CYCLES[opcode], "cycles of {:02X}", opcode);
+        assert_eq!(instruction.get_cycles(false), not_taken, "cycles of {:02X} without branching", opcode);
+    }
+}
+
+#[test]
+fn cb_opcodes_match_reference_lengths_and_cycles() {
+    for opcode in 0..256 {
+
+        // Operations on (HL) read and write memory, except
+        // BIT, which only reads it
+        let cycles = match (opcode & 0x07, opcode >> 6) {
+            (6, 1) => 12,
+            (6, _) => 16,
+            _ => 8
+        };
+        let (instruction, length) = decode_bytes(&[0xCB, opcode as u8]);
+
+        assert_eq!(length, 2, "length of CB {:02X}", opcode);
+        assert_eq!(instruction.get_cycles(true), cycles, "cycles of CB {:02X}", opcode);
+        assert_eq!(instruction.get_cycles(false), cycles, "cycles of CB {:02X}", opcode);
+    }
+}
+
+#[test]
+fn undefined_opcodes_decode_as_undefined() {
+    for &opcode in [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD].iter() {
+        match decode_bytes(&[opcode]).0 {
+            Instruction::Undefined(undefined) => assert_eq!(undefined, opcode),
+            _ => panic!("{:02X} should be undefined", opcode)
+        }
+    }
+}
@@ -0,0 +1,78 @@
+extern crate rusty_boy_dmg;
+
+mod common;
+
+use common::*;
+
+/// Handlers at each interrupt vector, which
+/// return straight away with interrupts on.
+fn reti_handlers() -> Vec<(u16, &'static [u8])> {
+    static RETI: [u8; 1] = [0xD9];
+    (0..5).map(|bit| (0x40 + bit * 8, &RETI[..])).collect()
+}
+
+#[test]
+fn interrupts_dispatch_one_at_a_time_by_priority() {
+    let code = [
+
+        // Turn the display off so it doesn't
+        // request any more interrupts
+        0xAF,          // xor a
+        0xE0, 0x40,    // ldh [$FF40], a
+
+        0x3E, 0x1F,    // ld a, $1F
+        0xE0, 0xFF,    // ldh [$FFFF], a
+        0xE0, 0x0F,    // ldh [$FF0F], a
+        0xFB,          // ei
+        0x00           // nop
+    ];
+    let mut rom = reti_handlers();
+    rom.push((0x100, &code));
+    let mut gameboy = boot(&rom);
+    run_to(&mut gameboy, 0x109, 10);
+
+    // EI takes effect after the NOP, then each RETI lets
+    // the next highest priority interrupt through
+    gameboy.step_instruction().unwrap();
+    gameboy.step_instruction().unwrap();
+    for bit in 0..5 {
+        assert_eq!(gameboy.cpu.get_reg_pc(), 0x40 + bit * 8);
+        assert_eq!(gameboy.memory_manager.read_memory(0xFF0F) & 0x1F, 0x1F & !((2 << bit) - 1));
+        gameboy.step_instruction().unwrap();
+    }
+    assert_eq!(gameboy.cpu.get_reg_pc(), 0x10B);
+}
+
+/// Runs code that sets SP to 0x0000, so the upper byte
+/// of PC is pushed over IE, enables the given
+/// interrupts, requests them and turns on IME.
+fn dispatch_over_ie(enabled: u8, requested: u8) -> u16 {
+    let code = [
+        0x31, 0x00, 0x00,    // ld sp, $0000
+        0x3E, enabled,       // ld a, enabled
+        0xE0, 0xFF,          // ldh [$FFFF], a
+        0x3E, requested,     // ld a, requested
+        0xE0, 0x0F,          // ldh [$FF0F], a
+        0xFB,                // ei
+        0x00                 // nop
+    ];
+    let mut rom = reti_handlers();
+    rom.push((0x100, &code));
+    let mut gameboy = boot(&rom);
+    run_to(&mut gameboy, 0x10C, 10);
+    gameboy.step_instruction().unwrap();
+
+    // IE now holds the upper byte of the return address
+    assert_eq!(gameboy.memory_manager.read_memory(0xFFFF), 0x01);
+    gameboy.cpu.get_reg_pc()
+}
+
+#[test]
+fn ie_overwritten_by_push_cancels_dispatch() {
+    assert_eq!(dispatch_over_ie(0x04, 0x04), 0x0000);
+}
+
+#[test]
+fn ie_overwritten_by_push_changes_interrupt() {
+    assert_eq!(dispatch_over_ie(0x04, 0x05), 0x0040);
+}
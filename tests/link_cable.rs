@@ -0,0 +1,121 @@
+extern crate rusty_boy_dmg;
+
+use rusty_boy_dmg::link_cable::*;
+use rusty_boy_dmg::serial::*;
+
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Connects two peers to each other over TCP on
+/// the loopback interface.
+fn connect_pair() -> (SocketLinkPeer, SocketLinkPeer) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let connecting = thread::spawn(move || SocketLinkPeer::from_tcp(TcpStream::connect(address).unwrap()).unwrap());
+    let (stream, _) = listener.accept().unwrap();
+    let listening = SocketLinkPeer::from_tcp(stream).unwrap();
+    (listening, connecting.join().unwrap())
+}
+
+/// Starts a transfer from master and polls it alongside
+/// slave until it finishes, returning the byte the
+/// master got back.
+fn clock_transfer<F>(master: &mut SocketLinkPeer, slave: &mut SocketLinkPeer, outgoing: u8, mut poll: F) -> u8
+    where F: FnMut(&mut SocketLinkPeer) {
+    let started = Instant::now();
+    master.start_transfer(outgoing);
+    loop {
+        assert!(started.elapsed() < Duration::from_secs(5), "transfer never finished");
+        poll(slave);
+        if let Some(incoming) = master.poll_transfer() {
+            return incoming;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn peers_exchange_bytes() {
+    let (mut master, mut slave) = connect_pair();
+    let mut received = None;
+    let incoming = clock_transfer(&mut master, &mut slave, 0x12, |slave| {
+        received = received.or(slave.poll_external(0x34));
+    });
+
+    assert_eq!(incoming, 0x34);
+    assert_eq!(received, Some(0x12));
+    assert!(master.is_connected() && slave.is_connected());
+}
+
+#[test]
+fn idle_slave_answers_without_losing_sync() {
+    let (mut master, mut slave) = connect_pair();
+
+    // The slave isn't ready, so it answers with its
+    // current byte and drops the one sent to it
+    let started = Instant::now();
+    let incoming = clock_transfer(&mut master, &mut slave, 0x12, |slave| slave.poll_idle(0x56));
+    assert_eq!(incoming, 0x56);
+    assert!(started.elapsed() < Duration::from_millis(900));
+
+    // The next transfer pairs up with the
+    // slave's next ready byte
+    let mut received = None;
+    let incoming = clock_transfer(&mut master, &mut slave, 0x9A, |slave| {
+        received = received.or(slave.poll_external(0xBC));
+    });
+    assert_eq!(incoming, 0xBC);
+    assert_eq!(received, Some(0x9A));
+}
+
+#[test]
+fn silent_peer_does_not_block_emulation() {
+    let (master, _slave) = connect_pair();
+    let mut serial = Serial::new();
+    serial.set_link_peer(Box::new(master));
+
+    // The transfer stays active past its bit timing while
+    // the peer hasn't answered, without stalling the caller
+    let started = Instant::now();
+    serial.write_register(0xFF01, 0x12);
+    serial.write_register(0xFF02, 0x81);
+    assert!(!serial.update(512 * 8));
+    assert!(started.elapsed() < Duration::from_millis(100));
+    assert_eq!(serial.read_register(0xFF02) & 0x80, 0x80);
+
+    // Once the reply times out the idle line is shifted in
+    thread::sleep(Duration::from_millis(1100));
+    assert!(serial.update(512));
+    assert_eq!(serial.read_register(0xFF01), 0xFF);
+    assert_eq!(serial.read_register(0xFF02) & 0x80, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn listen_replaces_stale_unix_socket() {
+    use std::os::unix::net::UnixListener;
+
+    let path = std::env::temp_dir().join(format!("rusty_boy_link_{}.sock", std::process::id()));
+    drop(UnixListener::bind(&path).unwrap());
+    let address = format!("unix:{}", path.display());
+
+    let listen_address = address.clone();
+    let listening = thread::spawn(move || SocketLinkPeer::listen(&listen_address).unwrap());
+    let started = Instant::now();
+    let connecting = loop {
+        if listening.is_finished() {
+            break None;
+        }
+        if let Ok(peer) = SocketLinkPeer::connect(&address) {
+            break Some(peer);
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "listener never came up");
+        thread::sleep(Duration::from_millis(1));
+    };
+    let listening = listening.join().unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert!(connecting.unwrap().is_connected());
+    assert!(listening.is_connected());
+}
@@ -0,0 +1,111 @@
+extern crate rusty_boy_dmg;
+
+use rusty_boy_dmg::png::*;
+use rusty_boy_dmg::printer::*;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+/// Commands sent in printer packets.
+static COMMAND_INIT: u8 = 0x01;
+static COMMAND_PRINT: u8 = 0x02;
+static COMMAND_DATA: u8 = 0x04;
+static COMMAND_STATUS: u8 = 0x0F;
+
+/// Returns an empty directory
+/// for a test's prints.
+fn output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusty_boy_printer_{}_{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Sends a packet to the printer and returns its
+/// responses to the keep alive and status bytes,
+/// checking it answers 0 to everything before them.
+fn send_packet(printer: &mut Printer, command: u8, compressed: bool, data: &[u8], corrupt: bool) -> (u8, u8) {
+    let mut packet = vec![0x88, 0x33, command, compressed as u8, data.len() as u8, (data.len() >> 8) as u8];
+    packet.extend_from_slice(data);
+    let checksum = packet[2..].iter().fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+    let checksum = if corrupt { checksum ^ 0x0100 } else { checksum };
+    packet.extend_from_slice(&[checksum as u8, (checksum >> 8) as u8]);
+
+    for byte in packet {
+        assert_eq!(printer.transfer(byte), 0x00);
+    }
+    (printer.transfer(0x00), printer.transfer(0x00))
+}
+
+#[test]
+fn decompress_expands_runs_and_literals() {
+    assert_eq!(decompress(&[0x81, 0xAA]), vec![0xAA; 3]);
+    assert_eq!(decompress(&[0x02, 0x01, 0x02, 0x03]), vec![0x01, 0x02, 0x03]);
+    assert_eq!(decompress(&[0x80, 0x55, 0x00, 0x66, 0xFF, 0x77]), [vec![0x55; 2], vec![0x66], vec![0x77; 129]].concat());
+}
+
+#[test]
+fn decompress_stops_at_truncated_data() {
+    assert_eq!(decompress(&[0x85]), Vec::<u8>::new());
+    assert_eq!(decompress(&[0x03, 0x01, 0x02]), vec![0x01, 0x02]);
+}
+
+#[test]
+fn packets_are_answered_with_alive_and_status() {
+    let mut printer = Printer::new(output_dir("status"));
+
+    assert_eq!(send_packet(&mut printer, COMMAND_INIT, false, &[], false), (0x81, 0x00));
+    assert_eq!(send_packet(&mut printer, COMMAND_DATA, false, &[0; 640], false), (0x81, 0x08));
+    assert_eq!(send_packet(&mut printer, COMMAND_STATUS, false, &[], true), (0x81, 0x09));
+    assert_eq!(send_packet(&mut printer, COMMAND_STATUS, false, &[], false), (0x81, 0x08));
+    assert_eq!(send_packet(&mut printer, COMMAND_DATA, false, &[], false), (0x81, 0x0C));
+}
+
+#[test]
+fn garbage_before_magic_is_ignored() {
+    let mut printer = Printer::new(output_dir("magic"));
+    for byte in [0x00, 0x88, 0x00, 0x33].iter() {
+        assert_eq!(printer.transfer(*byte), 0x00);
+    }
+
+    assert_eq!(send_packet(&mut printer, COMMAND_INIT, false, &[], false), (0x81, 0x00));
+}
+
+#[test]
+fn print_saves_png_and_reports_printing() {
+    let dir = output_dir("print");
+    let mut printer = Printer::new(dir.clone());
+    send_packet(&mut printer, COMMAND_INIT, false, &[], false);
+
+    // Two rows of tiles, sent compressed as runs of 0xFF
+    send_packet(&mut printer, COMMAND_DATA, true, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFA, 0xFF], false);
+    send_packet(&mut printer, COMMAND_DATA, false, &[], false);
+    assert_eq!(send_packet(&mut printer, COMMAND_PRINT, false, &[1, 0x00, 0xE4, 0x40], false), (0x81, 0x06));
+
+    // The printer is busy for a few status checks
+    for _ in 0..3 {
+        assert_eq!(send_packet(&mut printer, COMMAND_STATUS, false, &[], false), (0x81, 0x06));
+    }
+    assert_eq!(send_packet(&mut printer, COMMAND_STATUS, false, &[], false), (0x81, 0x00));
+
+    let png = fs::read(dir.join("print_0001.png")).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(&png[16..24], &[0, 0, 0, 160, 0, 0, 0, 16]);
+}
+
+#[test]
+fn empty_print_saves_nothing() {
+    let dir = output_dir("empty");
+    let mut printer = Printer::new(dir.clone());
+    send_packet(&mut printer, COMMAND_INIT, false, &[], false);
+    send_packet(&mut printer, COMMAND_PRINT, false, &[1, 0x00, 0xE4, 0x40], false);
+
+    assert!(!dir.exists());
+}
+
+#[test]
+fn encode_rgb_rejects_mismatched_data() {
+    assert!(encode_rgb(2, 2, &[0; 12]).is_ok());
+    assert!(encode_rgb(2, 2, &[0; 11]).is_err());
+    assert!(encode_rgb(160, 0, &[]).is_err());
+}
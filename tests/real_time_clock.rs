@@ -0,0 +1,62 @@
+extern crate rusty_boy_dmg;
+
+use rusty_boy_dmg::real_time_clock::*;
+
+/// Number of CPU cycles in one second.
+static CYCLES_PER_SECOND: i32 = 4194304;
+
+/// Creates a clock with its registers set, in
+/// the order seconds, minutes, hours, days.
+fn clock_at(registers: [u8; 5]) -> RealTimeClock {
+    let mut clock = RealTimeClock::new();
+    for (index, byte) in registers.iter().enumerate() {
+        clock.write_register(0x08 + index as u8, *byte);
+    }
+    clock
+}
+
+/// Reads every register, latching first.
+fn read_all(clock: &mut RealTimeClock) -> Vec<u8> {
+    clock.latch();
+    (0x08..0x0D).map(|register| clock.read_register(register)).collect()
+}
+
+#[test]
+fn advance_matches_ticking_each_second() {
+    let starts = [
+        [0, 0, 0, 0, 0],
+        [59, 59, 23, 0xFF, 0x01],
+        [62, 59, 23, 0x10, 0x00],
+        [30, 61, 3, 0x00, 0x00],
+        [10, 20, 27, 0x00, 0x01]
+    ];
+    for start in starts.iter() {
+        for &seconds in [1, 2, 59, 61, 3601, 86399, 86400, 90000].iter() {
+            let mut ticked = clock_at(*start);
+            for _ in 0..seconds {
+                ticked.update(CYCLES_PER_SECOND);
+            }
+            let mut advanced = clock_at(*start);
+            advanced.advance(seconds);
+
+            assert_eq!(read_all(&mut advanced), read_all(&mut ticked), "{:?} + {}s", start, seconds);
+        }
+    }
+}
+
+#[test]
+fn advance_past_day_counter_sets_carry() {
+    let mut clock = clock_at([0, 0, 0, 0, 0]);
+    clock.advance(1000 * 24 * 60 * 60 + 5);
+
+    // 1000 days wraps the 9-bit counter to 488, or 0x1E8
+    assert_eq!(read_all(&mut clock), vec![5, 0, 0, 0xE8, 0x81]);
+}
+
+#[test]
+fn advance_does_nothing_while_halted() {
+    let mut clock = clock_at([1, 2, 3, 4, 0x40]);
+    clock.advance(1 << 40);
+
+    assert_eq!(read_all(&mut clock), vec![1, 2, 3, 4, 0x40]);
+}
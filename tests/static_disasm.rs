@@ -0,0 +1,309 @@
+extern crate rusty_boy_dmg;
+
+use rusty_boy_dmg::disasm::*;
+use rusty_boy_dmg::static_disasm::*;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+/// Size of a ROM bank.
+static ROM_BANK_SIZE: usize = 0x4000;
+
+/// Code at 0x0150 that maps bank 2 and calls into it,
+/// then uses the forms an assembler could change.
+static MAIN: [u8; 22] = [
+    0x3E, 0x02,          // ld a, $02
+    0xEA, 0x00, 0x20,    // ld [$2000], a
+    0xCD, 0x00, 0x40,    // call $4000
+    0xE0, 0x80,          // ldh [$FF80], a
+    0xEA, 0x81, 0xFF,    // ld [$FF81], a
+    0xFA, 0x82, 0xFF,    // ld a, [$FF82]
+    0x10, 0x05,          // stop, padded with $05
+    0x20, 0xFC,          // jr nz, back to the stop
+    0x18, 0xFE           // jr @
+];
+
+/// Code at the start of bank 2.
+static BANK_2: [u8; 16] = [
+    0x21, 0x34, 0x12,    // ld hl, $1234
+    0x09,                // add hl, bc
+    0xF8, 0xFD,          // ld hl, sp-$03
+    0xE8, 0x05,          // add sp, $05
+    0xCB, 0x7E,          // bit 7, [hl]
+    0xCB, 0xC6,          // set 0, [hl]
+    0x2A,                // ld a, [hl+]
+    0x3A,                // ld a, [hl-]
+    0x76,                // halt
+    0xC9                 // ret
+];
+
+/// Builds a 64 KiB MBC1 ROM of pseudo-random
+/// data with the code above placed in it.
+fn build_rom() -> Vec<u8> {
+    let mut seed = 0x1234_5678u32;
+    let mut rom: Vec<u8> = (0..4 * ROM_BANK_SIZE).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    }).collect();
+
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x147] = 0x01;
+    rom[0x148] = 0x01;
+    rom[0x150..0x150 + MAIN.len()].copy_from_slice(&MAIN);
+    rom[0x2000..0x2040].copy_from_slice(&[0x00; 0x40]);
+    rom[2 * ROM_BANK_SIZE..2 * ROM_BANK_SIZE + BANK_2.len()].copy_from_slice(&BANK_2);
+    rom
+}
+
+/// Parses a number written as $hex or decimal,
+/// with an optional minus sign.
+fn parse_number(text: &str) -> i32 {
+    match text.strip_prefix('-') {
+        Some(text) => -parse_number(text),
+        None => match text.strip_prefix('$') {
+            Some(hex) => i32::from_str_radix(hex, 16).unwrap(),
+            None => text.parse().unwrap()
+        }
+    }
+}
+
+/// Assembles one instruction by finding the opcode and
+/// operand that the disassembler formats the same way.
+fn assemble_instruction(text: &str, address: u16) -> Vec<u8> {
+    let number = text.find('$').map(|start| {
+        let start = if text[..start].ends_with('-') { start - 1 } else { start };
+        let end = text[start + 1..].find(|c: char| !c.is_ascii_hexdigit() && c != '$')
+            .map(|end| start + 1 + end)
+            .unwrap_or(text.len());
+        parse_number(&text[start..end])
+    }).unwrap_or(0);
+
+    let operands = [
+        [number as u8, (number >> 8) as u8],
+        [number.wrapping_sub(address as i32 + 2) as u8, 0]
+    ];
+    let prefixes = (0..256).filter(|&opcode| opcode != 0xCB).map(|opcode| vec![opcode as u8])
+        .chain((0..256).map(|opcode| vec![0xCB, opcode as u8]));
+    for prefix in prefixes {
+        for operand in operands.iter() {
+            let bytes: Vec<u8> = prefix.iter().chain(operand.iter()).cloned().collect();
+            let line = disassemble(address, |read| bytes[(read - address) as usize]);
+            if line.text == text {
+                return line.bytes;
+            }
+        }
+    }
+    panic!("couldn't assemble \"{}\" at {:04X}", text, address);
+}
+
+/// Assembles the source written for each bank, the way
+/// rgbasm would, and returns the bytes of the ROM. This
+/// only covers the syntax the disassembler writes, so the
+/// real toolchain is checked separately when installed.
+fn assemble(sources: &[String]) -> Vec<u8> {
+
+    // Labels are placed on a first pass, where each one is
+    // assumed to be just after the instruction using it,
+    // which is in range of any jump
+    let mut labels: HashMap<String, Option<u16>> = HashMap::new();
+    for source in sources {
+        for line in source.lines().filter(|line| line.ends_with("::")) {
+            labels.insert(line.trim_end_matches(':').to_string(), None);
+        }
+    }
+
+    let mut rom = Vec::new();
+    for pass in 0..2 {
+        rom.clear();
+        let mut placed = HashMap::new();
+        for source in sources {
+            let mut address: u16 = if rom.is_empty() { 0x0000 } else { 0x4000 };
+            for line in source.lines() {
+                let line = line.split(';').next().unwrap().trim();
+                if line.is_empty() || line.starts_with("SECTION") {
+                    continue;
+                }
+                if line.ends_with("::") {
+                    placed.insert(line.trim_end_matches(':').to_string(), Some(address));
+                    continue;
+                }
+
+                let bytes = if let Some(operands) = line.strip_prefix("ds ") {
+                    let mut fields = operands.split(", ");
+                    let length = parse_number(fields.next().unwrap()) as usize;
+                    vec![parse_number(fields.next().unwrap()) as u8; length]
+                }
+                else if let Some(operands) = line.strip_prefix("db ") {
+                    operands.split(", ").map(|byte| parse_number(byte) as u8).collect()
+                }
+                else {
+                    let mut text = line.to_string();
+                    for (label, label_address) in labels.iter() {
+                        if text.ends_with(&format!(" {}", label)) {
+                            let label_address = label_address.unwrap_or(address.wrapping_add(2));
+                            text = format!("{}${:04X}", &text[..text.len() - label.len()], label_address);
+                        }
+                    }
+                    assemble_instruction(&text, address)
+                };
+                address = address.wrapping_add(bytes.len() as u16);
+                rom.extend_from_slice(&bytes);
+            }
+        }
+        if pass == 0 {
+            labels = placed;
+        }
+    }
+    rom
+}
+
+#[test]
+fn disassembly_rebuilds_rom_byte_for_byte() {
+    let rom = build_rom();
+    let analysis = RomAnalysis::analyze(&rom).unwrap();
+    let sources: Vec<String> = (0..analysis.get_bank_count()).map(|bank| analysis.write_bank(bank)).collect();
+
+    assert!(analysis.is_code(0x150) && analysis.is_code(2 * ROM_BANK_SIZE + 0x0F));
+    let rebuilt = assemble(&sources);
+    assert_eq!(rebuilt.len(), rom.len());
+    if let Some(offset) = (0..rom.len()).find(|&offset| rebuilt[offset] != rom[offset]) {
+        panic!("byte {:05X} is {:02X} instead of {:02X}", offset, rebuilt[offset], rom[offset]);
+    }
+}
+
+#[test]
+fn high_page_loads_are_written_as_data() {
+    let analysis = RomAnalysis::analyze(&build_rom()).unwrap();
+    let source = analysis.write_bank(0);
+
+    assert!(source.contains("    ldh [$FF80], a\n"));
+    assert!(source.contains("    db $EA, $81, $FF ; ld [$FF81], a\n"));
+    assert!(source.contains("    db $FA, $82, $FF ; ld a, [$FF82]\n"));
+    assert!(source.contains("    db $10, $05\n"));
+}
+
+#[test]
+fn wrapping_relative_jumps_are_written_as_data() {
+    let analysis = RomAnalysis::analyze(&build_rom()).unwrap();
+    let source = analysis.write_bank(0);
+
+    assert!(source.contains("    db $20, $C6 ; jr nz, $FFF9\n"));
+}
+
+#[test]
+fn halt_is_written_as_data() {
+    let analysis = RomAnalysis::analyze(&build_rom()).unwrap();
+    let source = analysis.write_bank(2);
+
+    assert!(source.contains("    db $76 ; halt\n"));
+    assert!(!source.contains("    halt\n"));
+}
+
+#[test]
+fn mbc2_writes_without_bit_8_keep_the_bank() {
+    let mut rom = vec![0x00; 8 * ROM_BANK_SIZE];
+    rom[0x147] = 0x05;
+    rom[0x148] = 0x02;
+    rom[0x101..0x104].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    let main = [
+        0x3E, 0x03,          // ld a, $03
+        0xEA, 0x00, 0x21,    // ld [$2100], a
+        0xFA, 0x00, 0xC0,    // ld a, [$C000]
+        0xEA, 0x00, 0x20,    // ld [$2000], a
+        0xC3, 0x00, 0x40     // jp $4000
+    ];
+    rom[0x150..0x150 + main.len()].copy_from_slice(&main);
+    rom[3 * ROM_BANK_SIZE] = 0xC9;    // ret
+
+    // Bank 3 is still mapped after the write to
+    // RAM enable, even though its value isn't known
+    let analysis = RomAnalysis::analyze(&rom).unwrap();
+    assert!(analysis.is_code(3 * ROM_BANK_SIZE));
+    assert!(analysis.write_bank(0).contains("jp Label_003_4000"));
+}
+
+#[test]
+fn known_values_are_forgotten_at_labels() {
+    let mut rom = vec![0x00; 4 * ROM_BANK_SIZE];
+    rom[0x147] = 0x01;
+    rom[0x148] = 0x01;
+    rom[0x101..0x104].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    let main = [
+        0x3E, 0x02,          // ld a, $02
+        0x20, 0x02,          // jr nz, $0156
+        0x3E, 0x03,          // ld a, $03
+
+        // 0x0156, reached with A either 2 or 3
+        0xEA, 0x00, 0x20,    // ld [$2000], a
+        0xC3, 0x00, 0x40     // jp $4000
+    ];
+    rom[0x150..0x150 + main.len()].copy_from_slice(&main);
+    rom[2 * ROM_BANK_SIZE] = 0xC9;    // ret
+    rom[3 * ROM_BANK_SIZE] = 0xC9;    // ret
+
+    // Neither bank can be picked, so the jump into
+    // 0x4000-0x7FFF isn't followed
+    let analysis = RomAnalysis::analyze(&rom).unwrap();
+    assert!(!analysis.is_code(2 * ROM_BANK_SIZE));
+    assert!(!analysis.is_code(3 * ROM_BANK_SIZE));
+}
+
+#[test]
+fn known_values_are_forgotten_at_labels_found_later() {
+    let mut rom = vec![0x00; 4 * ROM_BANK_SIZE];
+    rom[0x147] = 0x01;
+    rom[0x148] = 0x01;
+    rom[0x101..0x104].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    let main = [
+        0xCD, 0x70, 0x01,    // call $0170
+        0x3E, 0x02,          // ld a, $02
+
+        // 0x0155, also jumped to from 0x0170, which
+        // is walked after the code running through it
+        0x00,                // nop
+        0xEA, 0x00, 0x20,    // ld [$2000], a
+        0xC3, 0x00, 0x40     // jp $4000
+    ];
+    rom[0x150..0x150 + main.len()].copy_from_slice(&main);
+    rom[0x170..0x173].copy_from_slice(&[0xC3, 0x55, 0x01]);    // jp $0155
+    rom[2 * ROM_BANK_SIZE] = 0xC9;    // ret
+
+    let analysis = RomAnalysis::analyze(&rom).unwrap();
+    assert!(analysis.is_code(0x155));
+    assert!(!analysis.is_code(2 * ROM_BANK_SIZE));
+}
+
+/// Returns whether RGBDS is installed. CI sets RGBDS_REQUIRED,
+/// so a missing install fails there instead of skipping.
+fn has_rgbds() -> bool {
+    let found = Command::new("rgbasm").arg("--version").output().is_ok();
+    if !found && env::var_os("RGBDS_REQUIRED").is_some() {
+        panic!("rgbasm not found, but RGBDS_REQUIRED is set");
+    }
+    found
+}
+
+#[test]
+fn project_rebuilds_rom_with_rgbds() {
+    if !has_rgbds() {
+        eprintln!("rgbasm not found, skipping");
+        return;
+    }
+    let rom = build_rom();
+    let dir = env::temp_dir().join(format!("rusty_boy_disasm_{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    RomAnalysis::analyze(&rom).unwrap().write_project(&dir).unwrap();
+
+    let status = Command::new("make").current_dir(&dir).status().unwrap();
+    let rebuilt = fs::read(dir.join("game.gb"));
+    let _ = fs::remove_dir_all(&dir);
+    assert!(status.success());
+
+    let rebuilt = rebuilt.unwrap();
+    assert_eq!(rebuilt.len(), rom.len());
+    if let Some(offset) = (0..rom.len()).find(|&offset| rebuilt[offset] != rom[offset]) {
+        panic!("byte {:05X} is {:02X} instead of {:02X}", offset, rebuilt[offset], rom[offset]);
+    }
+}
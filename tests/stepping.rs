@@ -0,0 +1,61 @@
+extern crate rusty_boy_dmg;
+
+mod common;
+
+use common::*;
+use rusty_boy_dmg::gameboy::*;
+
+/// A loop of 16 cycle jumps at 0x0100.
+static JUMP_LOOP: [u8; 3] = [0xC3, 0x00, 0x01];    // jp $0100
+
+/// Cycles taken by the longest instruction.
+static MAX_INSTRUCTION_CYCLES: i32 = 24;
+
+#[test]
+fn step_instruction_runs_one_instruction() {
+    let mut gameboy = boot(&[(0x100, &[0x00, 0x00])]);
+    let result = gameboy.step_instruction().unwrap();
+
+    assert_eq!(result, RunResult { cycles: 4, reason: StopReason::InstructionComplete });
+    assert_eq!(gameboy.cpu.get_reg_pc(), 0x101);
+}
+
+#[test]
+fn run_cycles_stops_at_the_end_of_an_instruction() {
+    let mut gameboy = boot(&[(0x100, &JUMP_LOOP)]);
+
+    assert_eq!(gameboy.run_cycles(10).unwrap(), RunResult { cycles: 16, reason: StopReason::CyclesElapsed });
+    assert_eq!(gameboy.run_cycles(32).unwrap().cycles, 32);
+    assert_eq!(gameboy.run_cycles(33).unwrap().cycles, 48);
+    assert_eq!(gameboy.cpu.get_reg_pc(), 0x100);
+}
+
+#[test]
+fn run_frame_stops_at_vblank() {
+    let mut gameboy = boot(&[(0x100, &STRIPES)]);
+    gameboy.run_frame().unwrap();
+
+    for frame in 1..4 {
+        let result = gameboy.run_frame().unwrap();
+
+        assert_eq!(result.reason, StopReason::VBlank);
+        assert_eq!(gameboy.memory_manager.read_memory(0xFF44), 144);
+        assert_eq!(gameboy.get_frame_count(), frame + 1);
+        assert!((result.cycles - CYCLES_PER_FRAME).abs() < MAX_INSTRUCTION_CYCLES, "{} cycles", result.cycles);
+    }
+}
+
+#[test]
+fn run_frame_gives_up_with_the_display_off() {
+    let code = [
+        0xAF,          // xor a
+        0xE0, 0x40,    // ldh [$FF40], a
+        0x18, 0xFE     // jr @
+    ];
+    let mut gameboy = boot(&[(0x100, &code)]);
+    let result = gameboy.run_frame().unwrap();
+
+    assert_eq!(result.reason, StopReason::CyclesElapsed);
+    assert!(result.cycles >= CYCLES_PER_FRAME && result.cycles < CYCLES_PER_FRAME + MAX_INSTRUCTION_CYCLES);
+    assert_eq!(gameboy.get_frame_count(), 0);
+}
@@ -0,0 +1,93 @@
+extern crate rusty_boy_dmg;
+
+use rusty_boy_dmg::timer::*;
+
+/// Cycles between TIMA increments at 262144 Hz.
+static CYCLES_PER_TICK: i32 = 16;
+
+/// Creates a timer counting at 262144 Hz, with
+/// TIMA one increment away from overflowing.
+fn timer_about_to_overflow(tma: u8) -> Timer {
+    let mut timer = Timer::new();
+    timer.write_register(0xFF07, 0x05);
+    timer.write_register(0xFF06, tma);
+    timer.write_register(0xFF05, 0xFF);
+    timer
+}
+
+#[test]
+fn tima_reloads_one_machine_cycle_after_overflow() {
+    let mut timer = timer_about_to_overflow(0xAB);
+
+    // TIMA reads 0 for a machine cycle before
+    // TMA is copied in and the interrupt is due
+    assert!(!timer.update(CYCLES_PER_TICK));
+    assert_eq!(timer.read_register(0xFF05), 0x00);
+    assert!(timer.update(4));
+    assert_eq!(timer.read_register(0xFF05), 0xAB);
+}
+
+#[test]
+fn tima_write_before_reload_cancels_it() {
+    let mut timer = timer_about_to_overflow(0xAB);
+    timer.update(CYCLES_PER_TICK);
+    timer.write_register(0xFF05, 0x42);
+
+    assert!(!timer.update(4));
+    assert_eq!(timer.read_register(0xFF05), 0x42);
+}
+
+#[test]
+fn writes_during_reload_go_to_tma() {
+    let mut timer = timer_about_to_overflow(0xAB);
+    timer.update(CYCLES_PER_TICK);
+    timer.update(4);
+
+    // TIMA writes are ignored while it's being
+    // reloaded, but TMA writes go through to it
+    timer.write_register(0xFF05, 0x42);
+    assert_eq!(timer.read_register(0xFF05), 0xAB);
+    timer.write_register(0xFF06, 0x77);
+    assert_eq!(timer.read_register(0xFF05), 0x77);
+
+    // Once the reload is over, TIMA is written normally
+    timer.update(4);
+    timer.write_register(0xFF05, 0x42);
+    assert_eq!(timer.read_register(0xFF05), 0x42);
+}
+
+#[test]
+fn div_write_increments_tima_on_falling_edge() {
+    let mut timer = Timer::new();
+    timer.write_register(0xFF07, 0x05);
+
+    // Bit 3 of the counter is set, so resetting
+    // it gives TIMA a falling edge
+    timer.set_system_counter(0x0008);
+    timer.write_register(0xFF04, 0x00);
+    assert_eq!(timer.read_register(0xFF05), 1);
+    assert_eq!(timer.read_register(0xFF04), 0);
+
+    // With bit 3 clear there's no edge
+    timer.set_system_counter(0x0FF0);
+    timer.write_register(0xFF04, 0x00);
+    assert_eq!(timer.read_register(0xFF05), 1);
+}
+
+#[test]
+fn disabling_timer_increments_tima_on_falling_edge() {
+    let mut timer = Timer::new();
+    timer.write_register(0xFF07, 0x05);
+    timer.set_system_counter(0x0008);
+    timer.write_register(0xFF07, 0x01);
+
+    assert_eq!(timer.read_register(0xFF05), 1);
+}
+
+#[test]
+fn div_is_the_upper_byte_of_the_counter() {
+    let mut timer = Timer::new();
+    timer.update(256 * 3);
+
+    assert_eq!(timer.read_register(0xFF04), 3);
+}